@@ -0,0 +1,215 @@
+// Ligero / Brakedown-style multilinear commitment over a Reed-Solomon code.
+
+// The polynomial P is arranged in a matrix with 2^{num_vars - num_col_vars} rows of length 2^{num_col_vars}, so that
+// P(r) = sum_{i, j} eq(r_hi, i) eq(r_lo, j) M[i][j], where r_lo are the first num_col_vars coordinates of r.
+// Every row is encoded by the Reed-Solomon code with rate 2^{-log_blowup} (using additive NTT), and columns of the encoded matrix are
+// committed in a Merkle tree.
+// To open, prover sends the combination of rows with random coefficients (proximity test), and the combination of
+// rows with coefficients eq(r_hi, i) (evaluation row). Verifier checks that their encodings agree with random
+// columns of the committed matrix, and computes the value from the evaluation row.
+// Challenges are obtained by Fiat-Shamir, so the proof is non-interactive.
+
+use num_traits::Zero;
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{field::F128, hash::{challenges_from_seed, keccak256}, merkle::{verify_batch, Digest, KeccakHasher, MerkleTree}, ntt::AdditiveNtt, protocols::utils::eq_poly};
+
+use super::{CommitmentBytes, PolynomialCommitment};
+
+pub struct Ligero {
+    num_vars: usize,
+    num_col_vars: usize,
+    log_blowup: usize,
+    num_queries: usize,
+    ntt: AdditiveNtt,
+}
+
+#[derive(Clone, Debug)]
+pub struct LigeroCommitment {
+    pub root: Digest,
+}
+
+impl CommitmentBytes for LigeroCommitment {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.root.to_vec()
+    }
+}
+
+pub struct LigeroProverData {
+    rows: Vec<Vec<F128>>,
+    columns: Vec<Vec<F128>>, // Columns of the encoded matrix.
+    tree: MerkleTree,
+}
+
+#[derive(Clone, Debug)]
+pub struct LigeroProof {
+    pub proximity_row: Vec<F128>,
+    pub eval_row: Vec<F128>,
+    pub columns: Vec<Vec<F128>>,
+    pub merkle_proof: Vec<Digest>,
+}
+
+impl Ligero {
+    pub fn new(num_vars: usize, num_col_vars: usize, log_blowup: usize, num_queries: usize) -> Self {
+        assert!(num_col_vars <= num_vars);
+        assert!(log_blowup > 0);
+        Self { num_vars, num_col_vars, log_blowup, num_queries, ntt: AdditiveNtt::new(num_col_vars) }
+    }
+
+    pub fn num_rows(&self) -> usize {
+        1 << (self.num_vars - self.num_col_vars)
+    }
+
+    pub fn row_len(&self) -> usize {
+        1 << self.num_col_vars
+    }
+
+    pub fn codeword_len(&self) -> usize {
+        1 << (self.num_col_vars + self.log_blowup)
+    }
+
+    fn transcript_seed(&self, commitment: &LigeroCommitment, point: &[F128]) -> Digest {
+        let mut input = commitment.root.to_vec();
+        input.extend_from_slice(bytemuck::cast_slice(point));
+        keccak256(&input)
+    }
+
+    fn query_indices(&self, seed: &Digest, proximity_row: &[F128], eval_row: &[F128]) -> Vec<usize> {
+        let mut input = seed.to_vec();
+        input.extend_from_slice(bytemuck::cast_slice(proximity_row));
+        input.extend_from_slice(bytemuck::cast_slice(eval_row));
+        let seed = keccak256(&input);
+        challenges_from_seed(&seed, 1, self.num_queries).iter()
+            .map(|x| (x.raw() as usize) % self.codeword_len())
+            .collect()
+    }
+
+    /// Reed-Solomon encoding of a row, see AdditiveNtt::encode.
+    fn encode(&self, row: &[F128]) -> Vec<F128> {
+        self.ntt.encode(row, self.log_blowup)
+    }
+}
+
+fn combine_rows(rows: &[Vec<F128>], coeffs: &[F128]) -> Vec<F128> {
+    let mut ret = vec![F128::zero(); rows[0].len()];
+    for (row, c) in rows.iter().zip(coeffs.iter()) {
+        for (r, x) in ret.iter_mut().zip(row.iter()) {
+            *r += *c * x;
+        }
+    }
+    ret
+}
+
+fn combine_column(column: &[F128], coeffs: &[F128]) -> F128 {
+    column.iter().zip(coeffs.iter()).fold(F128::zero(), |acc, (a, b)| acc + *a * b)
+}
+
+impl PolynomialCommitment for Ligero {
+    type Commitment = LigeroCommitment;
+    type ProverData = LigeroProverData;
+    type Proof = LigeroProof;
+
+    fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    fn commit(&self, poly: &[F128]) -> (LigeroCommitment, LigeroProverData) {
+        assert!(poly.len() == 1 << self.num_vars);
+        let rows : Vec<Vec<F128>> = poly.chunks(self.row_len()).map(|row| row.to_vec()).collect();
+
+        #[cfg(not(feature = "parallel"))]
+        let encoded : Vec<Vec<F128>> = rows.iter().map(|row| self.encode(row)).collect();
+        #[cfg(feature = "parallel")]
+        let encoded : Vec<Vec<F128>> = rows.par_iter().map(|row| self.encode(row)).collect();
+
+        #[cfg(not(feature = "parallel"))]
+        let iter = 0..self.codeword_len();
+        #[cfg(feature = "parallel")]
+        let iter = (0..self.codeword_len()).into_par_iter();
+
+        let columns : Vec<Vec<F128>> = iter.map(|j| encoded.iter().map(|row| row[j]).collect()).collect();
+
+        let tree = MerkleTree::new(&columns);
+        (LigeroCommitment { root: tree.root() }, LigeroProverData { rows, columns, tree })
+    }
+
+    fn open(&self, data: &LigeroProverData, point: &[F128]) -> LigeroProof {
+        assert!(point.len() == self.num_vars);
+        let seed = self.transcript_seed(&LigeroCommitment { root: data.tree.root() }, point);
+        let proximity_coeffs = challenges_from_seed(&seed, 0, self.num_rows());
+        let proximity_row = combine_rows(&data.rows, &proximity_coeffs);
+        let eval_row = combine_rows(&data.rows, &eq_poly(&point[self.num_col_vars..]));
+
+        let queries = self.query_indices(&seed, &proximity_row, &eval_row);
+        let columns = queries.iter().map(|&q| data.columns[q].clone()).collect();
+        let merkle_proof = data.tree.open_batch(&queries);
+
+        LigeroProof { proximity_row, eval_row, columns, merkle_proof }
+    }
+
+    fn verify(&self, commitment: &LigeroCommitment, point: &[F128], value: F128, proof: &LigeroProof) -> bool {
+        let LigeroProof { proximity_row, eval_row, columns, merkle_proof } = proof;
+        if point.len() != self.num_vars
+            || proximity_row.len() != self.row_len()
+            || eval_row.len() != self.row_len()
+            || columns.len() != self.num_queries
+            || columns.iter().any(|column| column.len() != self.num_rows()) {
+            return false;
+        }
+
+        let eq_lo = eq_poly(&point[..self.num_col_vars]);
+        if combine_column(eval_row, &eq_lo) != value {
+            return false;
+        }
+
+        let seed = self.transcript_seed(commitment, point);
+        let proximity_coeffs = challenges_from_seed(&seed, 0, self.num_rows());
+        let eq_hi = eq_poly(&point[self.num_col_vars..]);
+        let queries = self.query_indices(&seed, proximity_row, eval_row);
+
+        let encoded_proximity = self.encode(proximity_row);
+        let encoded_eval = self.encode(eval_row);
+
+        let depth = self.num_col_vars + self.log_blowup;
+        verify_batch::<KeccakHasher>(&commitment.root, depth, &queries, columns, merkle_proof)
+            && queries.iter().zip(columns.iter()).all(|(&q, column)| {
+                combine_column(column, &proximity_coeffs) == encoded_proximity[q]
+                    && combine_column(column, &eq_hi) == encoded_eval[q]
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::One;
+    use rand::rngs::OsRng;
+
+    use crate::protocols::utils::evaluate;
+
+    use super::*;
+
+    #[test]
+    fn ligero_open_verify() {
+        let rng = &mut OsRng;
+        let num_vars = 12;
+        let pcs = Ligero::new(num_vars, 6, 1, 40);
+
+        let poly : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let point : Vec<_> = (0 .. num_vars).map(|_| F128::rand(rng)).collect();
+        let value = evaluate(&poly, &point);
+
+        let (commitment, data) = pcs.commit(&poly);
+        let proof = pcs.open(&data, &point);
+        assert!(pcs.verify(&commitment, &point, value, &proof));
+
+        assert!(!pcs.verify(&commitment, &point, value + F128::one(), &proof));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.eval_row[0] += F128::one();
+        assert!(!pcs.verify(&commitment, &point, combine_column(&bad_proof.eval_row, &eq_poly(&point[..6])), &bad_proof));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.columns[0][0] += F128::one();
+        assert!(!pcs.verify(&commitment, &point, value, &bad_proof));
+    }
+}
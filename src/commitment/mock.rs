@@ -0,0 +1,126 @@
+// Mock oracle commitment, used for testing the composition of protocols.
+
+// Commitment is just a handle to the polynomial stored inside of the scheme, and the verifier evaluates it directly,
+// as if it had oracle access. Every checked opening claim is recorded, so tests can inspect which claims were
+// produced by the protocol. Obviously, this provides no succinctness and no hiding.
+
+use std::sync::Mutex;
+
+use crate::{field::F128, protocols::utils::evaluate};
+
+use super::{CommitmentBytes, PolynomialCommitment};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MockHandle {
+    pub id: usize,
+}
+
+impl CommitmentBytes for MockHandle {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.id.to_le_bytes().to_vec()
+    }
+}
+
+/// Opening claim value = P_id(point), together with the result of the check.
+#[derive(Clone, Debug)]
+pub struct OpeningClaim {
+    pub id: usize,
+    pub point: Vec<F128>,
+    pub value: F128,
+    pub accepted: bool,
+}
+
+pub struct MockCommitment {
+    num_vars: usize,
+    polys: Mutex<Vec<Vec<F128>>>,
+    claims: Mutex<Vec<OpeningClaim>>,
+}
+
+impl MockCommitment {
+    pub fn new(num_vars: usize) -> Self {
+        Self { num_vars, polys: Mutex::new(vec![]), claims: Mutex::new(vec![]) }
+    }
+
+    /// Honestly evaluates the committed polynomial (oracle query).
+    pub fn evaluate(&self, commitment: &MockHandle, point: &[F128]) -> F128 {
+        assert!(point.len() == self.num_vars);
+        evaluate(&self.polys.lock().unwrap()[commitment.id], point)
+    }
+
+    pub fn num_committed(&self) -> usize {
+        self.polys.lock().unwrap().len()
+    }
+
+    /// Returns all opening claims checked so far, in order.
+    pub fn opening_claims(&self) -> Vec<OpeningClaim> {
+        self.claims.lock().unwrap().clone()
+    }
+}
+
+impl PolynomialCommitment for MockCommitment {
+    type Commitment = MockHandle;
+    type ProverData = MockHandle;
+    type Proof = ();
+
+    fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    fn commit(&self, poly: &[F128]) -> (MockHandle, MockHandle) {
+        assert!(poly.len() == 1 << self.num_vars);
+        let mut polys = self.polys.lock().unwrap();
+        let handle = MockHandle { id: polys.len() };
+        polys.push(poly.to_vec());
+        (handle, handle)
+    }
+
+    fn open(&self, _data: &MockHandle, point: &[F128]) {
+        assert!(point.len() == self.num_vars);
+    }
+
+    fn verify(&self, commitment: &MockHandle, point: &[F128], value: F128, _proof: &()) -> bool {
+        let accepted = point.len() == self.num_vars
+            && commitment.id < self.num_committed()
+            && self.evaluate(commitment, point) == value;
+        self.claims.lock().unwrap().push(OpeningClaim { id: commitment.id, point: point.to_vec(), value, accepted });
+        accepted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use num_traits::One;
+    use rand::rngs::OsRng;
+
+    use crate::protocols::pipeline::{EvalClaim, OpeningStage, Stage};
+
+    use super::*;
+
+    #[test]
+    fn mock_records_claims() {
+        let rng = &mut OsRng;
+        let num_vars = 8;
+        let pcs = MockCommitment::new(num_vars);
+
+        let polys : Vec<Vec<F128>> = (0..3).map(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect()).collect();
+        let (commitments, data) : (Vec<_>, Vec<_>) = polys.iter().map(|p| pcs.commit(p)).unzip();
+        assert!(pcs.num_committed() == 3);
+
+        let point : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let values : Vec<_> = polys.iter().map(|p| evaluate(p, &point)).collect();
+
+        OpeningStage::new(&pcs, commitments.clone(), data.iter().collect()).run(EvalClaim { point: point.clone(), values: values.clone() }, rng);
+
+        pcs.open(&data[1], &point);
+        assert!(!pcs.verify(&commitments[1], &point, values[1] + F128::one(), &()));
+
+        let claims = pcs.opening_claims();
+        assert!(claims.len() == 4);
+        for i in 0..3 {
+            assert!(claims[i].id == i && claims[i].point == point && claims[i].value == values[i] && claims[i].accepted);
+        }
+        assert!(claims[3].id == 1 && !claims[3].accepted);
+    }
+}
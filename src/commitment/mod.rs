@@ -0,0 +1,32 @@
+// Polynomial commitment schemes for multilinear polynomials over F128.
+
+use crate::field::F128;
+
+pub mod ligero;
+pub mod mock;
+pub mod packed;
+
+/// Serialization of a commitment. Protocols on top of a PCS absorb it into their Fiat-Shamir transcripts, so that
+/// challenges depend on what was committed.
+pub trait CommitmentBytes {
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+/// Multilinear polynomial commitment scheme. Polynomials are given by their values on the boolean hypercube,
+/// in the same order as everywhere else in the crate (i-th bit of an index corresponds to i-th coordinate of a point).
+pub trait PolynomialCommitment {
+    /// Data sent to the verifier during the commitment phase.
+    type Commitment: Clone + CommitmentBytes;
+    /// Data kept by the prover to answer opening requests.
+    type ProverData;
+    type Proof;
+
+    fn num_vars(&self) -> usize;
+
+    fn commit(&self, poly: &[F128]) -> (Self::Commitment, Self::ProverData);
+
+    /// Proves that the committed polynomial evaluates to a given value in the point.
+    fn open(&self, data: &Self::ProverData, point: &[F128]) -> Self::Proof;
+
+    fn verify(&self, commitment: &Self::Commitment, point: &[F128], value: F128, proof: &Self::Proof) -> bool;
+}
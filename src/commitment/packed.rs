@@ -0,0 +1,193 @@
+// Commitment to packed boolean data (Binius-style small-field commitment).
+
+// Up to 128 boolean columns P_0, ..., P_127 are packed into a single F128 polynomial P = sum_i b_i P_i, where b_i is
+// the i-th basis element, and only P is committed with the underlying PCS.
+// Coordinate openings P_i(r) are answered as follows: the verifier twists the claimed coordinates into evaluations
+// of P in the inverse Frobenius orbit of r (see twist_evals), then the prover reduces these 128 evaluations to
+// a single evaluation of P using MulticlaimCheck, which is finally opened by the underlying PCS.
+// Challenges are obtained by Fiat-Shamir from the commitment, the point and the claimed coordinates.
+
+use crate::{
+    field::F128,
+    protocols::{multiclaim::{multiclaim_final_claim, MulticlaimCheck}, utils::{coordinate_evals, evaluate_univar, twist_evals}},
+    traits::CompressedPoly,
+    transcript::{prove_sumcheck, verify_sumcheck, Transcript},
+};
+
+use super::{CommitmentBytes, PolynomialCommitment};
+
+/// Packs boolean columns into a single polynomial, i-th column going into i-th coordinate.
+pub fn pack_columns(columns: &[Vec<bool>]) -> Vec<F128> {
+    assert!(!columns.is_empty() && columns.len() <= 128);
+    let l = columns[0].len();
+    assert!(l.is_power_of_two());
+    for column in columns {
+        assert!(column.len() == l);
+    }
+
+    (0..l).map(|x| {
+        let mut raw = 0u128;
+        for (i, column) in columns.iter().enumerate() {
+            raw |= (column[x] as u128) << i;
+        }
+        F128::from_raw(raw)
+    }).collect()
+}
+
+pub struct PackedCommitment<P: PolynomialCommitment> {
+    pcs: P,
+}
+
+pub struct PackedProverData<D> {
+    poly: [Vec<F128>; 1],
+    data: D,
+    commitment_bytes: Vec<u8>, // Serialized commitment, absorbed into the transcript of openings.
+}
+
+impl<D> PackedProverData<D> {
+    pub fn packed_poly(&self) -> &[F128] {
+        &self.poly[0]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PackedOpeningProof<Pr> {
+    /// Round polynomials of MulticlaimCheck.
+    pub round_msgs: Vec<CompressedPoly>,
+    /// Evaluation of P in the point produced by MulticlaimCheck.
+    pub value: F128,
+    pub proof: Pr,
+}
+
+impl<P: PolynomialCommitment> PackedCommitment<P> {
+    pub fn new(pcs: P) -> Self {
+        Self { pcs }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.pcs.num_vars()
+    }
+
+    pub fn commit_columns(&self, columns: &[Vec<bool>]) -> (P::Commitment, PackedProverData<P::ProverData>) {
+        self.commit(pack_columns(columns))
+    }
+
+    /// Commits to an already packed polynomial.
+    pub fn commit(&self, poly: Vec<F128>) -> (P::Commitment, PackedProverData<P::ProverData>) {
+        assert!(poly.len() == 1 << self.num_vars());
+        let (commitment, data) = self.pcs.commit(&poly);
+        let commitment_bytes = commitment.to_bytes();
+        (commitment, PackedProverData { poly: [poly], data, commitment_bytes })
+    }
+
+    fn transcript(commitment_bytes: &[u8], point: &[F128], coords: &[F128]) -> Transcript {
+        let mut transcript = Transcript::new(b"packed_opening");
+        transcript.absorb_bytes(commitment_bytes);
+        transcript.absorb(point);
+        transcript.absorb(coords);
+        transcript
+    }
+
+    /// Returns evaluations of all 128 coordinate polynomials P_i in the point, and the proof of their correctness.
+    pub fn open(&self, data: &PackedProverData<P::ProverData>, point: &[F128]) -> (Vec<F128>, PackedOpeningProof<P::Proof>) {
+        let num_vars = self.num_vars();
+        assert!(point.len() == num_vars);
+        let poly = data.packed_poly();
+
+        let coords = coordinate_evals(poly, point);
+        let mut frob_evals = coords.clone();
+        twist_evals(&mut frob_evals);
+
+        let mut transcript = Self::transcript(&data.commitment_bytes, point, &coords);
+        let gamma = transcript.challenge();
+        let mut prover = MulticlaimCheck::new(&data.poly, point.to_vec(), frob_evals).folding_challenge(gamma);
+
+        let (round_msgs, rs) = prove_sumcheck(&mut prover, num_vars, &mut transcript);
+        let value = prover.finish()[0];
+        let proof = self.pcs.open(&data.data, &rs);
+
+        (coords, PackedOpeningProof { round_msgs, value, proof })
+    }
+
+    /// Checks that coordinate polynomials of the committed polynomial evaluate to coords in the point.
+    pub fn verify(&self, commitment: &P::Commitment, point: &[F128], coords: &[F128], proof: &PackedOpeningProof<P::Proof>) -> bool {
+        let num_vars = self.num_vars();
+        if point.len() != num_vars || coords.len() != 128 || proof.round_msgs.len() != num_vars {
+            return false;
+        }
+
+        let mut frob_evals = coords.to_vec();
+        twist_evals(&mut frob_evals);
+
+        let mut transcript = Self::transcript(&commitment.to_bytes(), point, coords);
+        let gamma = transcript.challenge();
+        let Some((claim, rs)) = verify_sumcheck(evaluate_univar(&frob_evals, gamma), &proof.round_msgs, 2, &mut transcript) else {
+            return false;
+        };
+
+        multiclaim_final_claim(point, &rs, gamma, &[proof.value]) == claim
+            && self.pcs.verify(commitment, &rs, proof.value, &proof.proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use num_traits::One;
+    use rand::{rngs::OsRng, Rng};
+
+    use crate::{commitment::{ligero::Ligero, mock::MockCommitment}, protocols::utils::{evaluate, untwist_evals}};
+
+    use super::*;
+
+    #[test]
+    fn packed_open_verify() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+        let columns : Vec<Vec<bool>> = (0..128).map(|_| repeat_with(|| rng.gen_bool(0.5)).take(1 << num_vars).collect()).collect();
+        let point : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+
+        let packed = PackedCommitment::new(Ligero::new(num_vars, 5, 1, 40));
+        let (commitment, data) = packed.commit_columns(&columns);
+        let (coords, proof) = packed.open(&data, &point);
+
+        for i in 0..128 {
+            let column : Vec<_> = columns[i].iter().map(|b| F128::new(*b)).collect();
+            assert!(coords[i] == evaluate(&column, &point));
+        }
+
+        let mut frob_evals = coords.clone();
+        twist_evals(&mut frob_evals);
+        untwist_evals(&mut frob_evals);
+        assert!(frob_evals == coords);
+
+        assert!(packed.verify(&commitment, &point, &coords, &proof));
+
+        let mut wrong_coords = coords.clone();
+        wrong_coords[17] += F128::one();
+        assert!(!packed.verify(&commitment, &point, &wrong_coords, &proof));
+
+        // The proof is bound to the commitment it was produced for.
+        let other_columns : Vec<Vec<bool>> = (0..128).map(|_| repeat_with(|| rng.gen_bool(0.5)).take(1 << num_vars).collect()).collect();
+        let (other_commitment, _) = packed.commit_columns(&other_columns);
+        assert!(!packed.verify(&other_commitment, &point, &coords, &proof));
+    }
+
+    #[test]
+    fn challenges_depend_on_commitment() {
+        let rng = &mut OsRng;
+        let num_vars = 6;
+        let point : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let coords : Vec<_> = repeat_with(|| F128::rand(rng)).take(128).collect();
+
+        // Commitments to different polynomials, with the same point and the same claimed coordinates.
+        let packed = PackedCommitment::new(MockCommitment::new(num_vars));
+        let (a, _) = packed.commit(repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect());
+        let (b, _) = packed.commit(repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect());
+
+        let gamma_a = PackedCommitment::<MockCommitment>::transcript(&a.to_bytes(), &point, &coords).challenge();
+        let gamma_b = PackedCommitment::<MockCommitment>::transcript(&b.to_bytes(), &point, &coords).challenge();
+        assert!(gamma_a != gamma_b);
+    }
+}
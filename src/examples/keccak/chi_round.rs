@@ -1,7 +1,7 @@
 use num_traits::{One, Zero};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{field::F128, protocols::boolcheck::FnPackage, ptr_utils::{AsSharedMUMutPtr, UninitArr, UnsafeIndexRawMut}};
+use crate::{examples::keccak::matrices::{position, BATCH_VARS, STATES_PER_BATCH}, field::F128, hash::KECCAK_ROUND_CONSTANTS, protocols::{boolcheck::FnPackage, utils::evaluate}, ptr_utils::{AsSharedMUMutPtr, UninitArr, UnsafeIndexRawMut}};
 
 /// 111111111...1 in standard basis
 fn neg(x: F128) -> F128 {
@@ -107,6 +107,56 @@ impl FnPackage<5, 5> for ChiPackage {
     }
 }
 
+/// Round constant of iota step, in the layout of a single batch (see matrices.rs): it is added to the lane (0, 0)
+/// of every state of the polynomial 0. All 128 instances packed into F128 get the same constant.
+pub fn iota_constant(round: usize) -> Vec<F128> {
+    let rc = KECCAK_ROUND_CONSTANTS[round];
+    let mut ret = vec![F128::zero(); 1 << BATCH_VARS];
+    for j in 0..STATES_PER_BATCH {
+        for z in 0..64 {
+            if (rc >> z) & 1 == 1 {
+                ret[position(0, j, 0, z)] = F128::from_raw(u128::MAX);
+            }
+        }
+    }
+    ret
+}
+
+/// Chi followed by iota of the given round. Iota is the constant term of the formula, depending on the position.
+pub struct ChiIotaPackage {
+    constant: Vec<F128>,
+}
+
+impl ChiIotaPackage {
+    pub fn new(round: usize) -> Self {
+        Self { constant: iota_constant(round) }
+    }
+}
+
+impl FnPackage<5, 5> for ChiIotaPackage {
+    fn exec_lin_compressed(&self, arg: [F128; 5]) -> [F128; 5] {
+        chi_lin_compressed(arg)
+    }
+
+    fn exec_quad_compressed(&self, arg: [F128; 5]) -> [F128; 5] {
+        chi_quad_compressed(arg)
+    }
+
+    fn exec_alg(&self, data: &[F128], start: usize, offset: usize) -> [[F128; 5]; 3] {
+        chi_algebraic(data, start, offset)
+    }
+
+    fn exec_const_compressed(&self, x: usize) -> [F128; 5] {
+        [self.constant[x % (1 << BATCH_VARS)], F128::zero(), F128::zero(), F128::zero(), F128::zero()]
+    }
+
+    /// The constant does not depend on the variables above the batch, so its extension does not depend on them too.
+    fn const_ev(&self, pt: &[F128]) -> [F128; 5] {
+        assert!(pt.len() >= BATCH_VARS);
+        [evaluate(&self.constant, &pt[..BATCH_VARS]), F128::zero(), F128::zero(), F128::zero(), F128::zero()]
+    }
+}
+
 pub fn chi_round_witness(polys: &[Vec<F128>; 5]) -> [Vec<F128>; 5] {
     let l = polys[0].len();
     for i in 1..5 {
@@ -148,7 +198,7 @@ mod tests {
     use num_traits::One;
     use rand::rngs::OsRng;
 
-    use crate::{protocols::{boolcheck::{BoolCheck, BoolCheckOutput}, utils::{compute_trit_mappings, eq_ev, evaluate, evaluate_univar, extend_n_tables, untwist_evals}}, traits::SumcheckObject, utils::u128_idx};
+    use crate::{protocols::{boolcheck::{BoolCheck, BoolCheckOutput}, layered::quadratic_layer_witness, pipeline::{BoolCheckStage, EvalClaim, MulticlaimStage, Stage}, utils::{compute_trit_mappings, eq_ev, evaluate, evaluate_univar, extend_n_tables, untwist_evals}}, traits::SumcheckObject, utils::u128_idx};
 
     use super::*;
 
@@ -297,4 +347,30 @@ mod tests {
         println!("Time elapsed: {} ms", (end-start).as_millis());
     }
 
+    #[test]
+    fn chi_iota_boolcheck() {
+        let rng = &mut OsRng;
+        let num_vars = 12;
+        let f = ChiIotaPackage::new(3);
+
+        let polys : [Vec<F128>; 5] = std::array::from_fn(|_| (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect());
+        let output = quadratic_layer_witness(&polys, &f);
+        let chi = chi_round_witness(&polys);
+        for x in 0 .. 1 << num_vars {
+            assert!(output[0][x] == chi[0][x] + iota_constant(3)[x % (1 << BATCH_VARS)]);
+        }
+        assert!(output[1..] == chi[1..]);
+
+        let constant : Vec<_> = (0 .. 1 << num_vars).map(|x| f.exec_const_compressed(x)[0]).collect();
+        let pt : Vec<F128> = (0..num_vars).map(|_| F128::rand(rng)).collect();
+        assert!(f.const_ev(&pt)[0] == evaluate(&constant, &pt));
+
+        let values = output.iter().map(|poly| evaluate(poly, &pt)).collect();
+        let claim = BoolCheckStage::new(&f, polys.clone(), 5).run(EvalClaim { point: pt, values }, rng);
+        let claim = MulticlaimStage::new(&polys).run(claim, rng);
+        for (poly, value) in polys.iter().zip(claim.values.iter()) {
+            assert!(evaluate(poly, &claim.point) == *value);
+        }
+    }
+
 }
\ No newline at end of file
@@ -8,7 +8,19 @@
 
 use num_traits::Zero;
 
-use crate::{field::F128, protocols::lincheck::{Composition, IdentityMatrix, LinOp, MatrixSum}};
+use crate::{field::F128, protocols::linop::{BlockDiagonal, Composition, Embed, IdentityMatrix, LinOp, MatrixSum, Permutation, XorGate, XorNetworkLinOp}};
+
+/// Number of variables of a batch.
+pub const BATCH_VARS: usize = 10;
+/// Number of keccak states in a batch.
+pub const STATES_PER_BATCH: usize = 3;
+/// Number of elements of a single state in each polynomial (one for every (y, z)).
+pub const STATE_SIZE: usize = 320;
+
+/// Position of the element (x, y, z) of j-th state of the batch in the x-th polynomial.
+pub fn position(batch: usize, j: usize, y: usize, z: usize) -> usize {
+    (batch << BATCH_VARS) + j * STATE_SIZE + y * 64 + z
+}
 
 fn idx(x: usize, y: usize, z: usize) -> usize {
     x * 320 + y * 64 + z
@@ -191,14 +203,58 @@ impl LinOp for ThetaMatrix {
 }
 
 
+/// Linear operations of keccak (theta, rho and pi), described as an XOR network.
+/// Register layout: A (input, 1600), C (320), rotated C (320), D (320), E (1600), B (output, 1600).
+pub fn keccak_linear_network() -> XorNetworkLinOp {
+    let c = 1600;
+    let c_rot = c + 320;
+    let d = c_rot + 320;
+    let e = d + 320;
+    let b = e + 1600;
+
+    let mut gates = vec![];
+
+    // C[x] = A[x, 0] + A[x, 1] + A[x, 2] + A[x, 3] + A[x, 4]
+    for x in 0..5 {
+        for z in 0..64 {
+            gates.push(XorGate::Xor { dst: c + x * 64 + z, srcs: (0..5).map(|y| idx(x, y, z)).collect() });
+        }
+    }
+    // D[x] = C[x-1] + rot(C[x + 1], 1)
+    for x in 0..5 {
+        gates.push(XorGate::Rotate { dst: c_rot + x * 64, src: c + x * 64, len: 64, shift: 1 });
+    }
+    for x in 0..5 {
+        for z in 0..64 {
+            gates.push(XorGate::Xor { dst: d + x * 64 + z, srcs: vec![c + ((x + 4) % 5) * 64 + z, c_rot + ((x + 1) % 5) * 64 + z] });
+        }
+    }
+    // E[x, y] = A[x, y] + D[x]
+    for x in 0..5 {
+        for y in 0..5 {
+            for z in 0..64 {
+                gates.push(XorGate::Xor { dst: e + idx(x, y, z), srcs: vec![idx(x, y, z), d + x * 64 + z] });
+            }
+        }
+    }
+    // B[y, 2x + 3y] = rot(E[x, y], r[x, y])
+    for x in 0..5 {
+        for y in 0..5 {
+            gates.push(XorGate::Rotate { dst: b + idx(y, (2*x + 3*y) % 5, 0), src: e + idx(x, y, 0), len: 64, shift: ROTATIONS[x][y] });
+        }
+    }
+
+    XorNetworkLinOp::new(1600, b + 1600, gates, (b .. b + 1600).collect())
+}
+
 /// 1600 x 1600 matrix, implementing linear operations of keccak.
 pub struct KeccakLinMatrixUnbatched {
-    m: Composition<RhoPiMatrix, ThetaMatrix>,
+    m: XorNetworkLinOp,
 }
 
 impl KeccakLinMatrixUnbatched {
     pub fn new() -> Self {
-        Self { m: Composition::new(RhoPiMatrix{}, ThetaMatrix::new()) }
+        Self { m: keccak_linear_network() }
     }
 }
 
@@ -224,75 +280,49 @@ impl LinOp for KeccakLinMatrixUnbatched {
 /// each 1024 batch is split into 3 pieces of size 320, which are united together into 3 vectors of size 1600 (
 /// and on these we act with Keccak linear transforms).
 /// The remaining tail of size 64 is filled with zeros.
+///
+/// It is assembled as P^{-1} * (diag(K, K, K) padded with zeros) * P, where P moves data from our layout into
+/// 3 consecutive keccak states followed by 5 tails.
 pub struct KeccakLinMatrix {
-    m: KeccakLinMatrixUnbatched,
+    m: Composition<Permutation, Composition<Embed<BlockDiagonal<KeccakLinMatrixUnbatched>>, Permutation>>,
 }
 
 impl KeccakLinMatrix {
     pub fn new() -> Self {
-        Self { m: KeccakLinMatrixUnbatched::new() }
+        // to_state[s] is the index in our layout from which s-th element of the state layout is read.
+        let to_state = Permutation::new((0 .. 5 * 1024).map(|s| {
+            if s < 3 * 1600 {
+                let (j, i, k) = (s / 1600, (s % 1600) / 320, s % 320);
+                i * 1024 + j * 320 + k
+            } else {
+                let (i, k) = ((s - 3 * 1600) / 64, s % 64);
+                i * 1024 + 3 * 320 + k
+            }
+        }).collect());
+        let from_state = to_state.inverse();
+
+        let states = BlockDiagonal::new((0..3).map(|_| KeccakLinMatrixUnbatched::new()).collect());
+        let states = Embed::zero_pad(states, 5 * 1024, 5 * 1024);
+
+        Self { m: Composition::new(from_state, Composition::new(states, to_state)) }
     }
 }
 
 impl LinOp for KeccakLinMatrix {
     fn n_in(&self) -> usize {
-        5 * 1024
+        self.m.n_in()
     }
 
     fn n_out(&self) -> usize {
-        5 * 1024
+        self.m.n_out()
     }
 
     fn apply(&self, input: &[F128], output: &mut [F128]) {
-        let mut state = vec![vec![F128::zero(); 1600]; 3];
-        for i in 0..5 {
-            for j in 0..3 {
-                state[j][i * 320 .. (i + 1) * 320].copy_from_slice(
-                    &input[i * 1024 + j * 320 .. i * 1024 + (j + 1) * 320]
-                );
-            }
-        }
-
-        let mut output_state = vec![vec![F128::zero(); 1600]; 3];
-
-        for j in 0..3 {
-            self.m.apply(&state[j], &mut output_state[j]);
-        }
-
-        for i in 0..5 {
-            for j in 0..3 {
-                output[i * 1024 + j * 320 .. i * 1024 + (j + 1) * 320].copy_from_slice(
-                    &output_state[j][i * 320 .. (i + 1) * 320]
-                );
-            }
-        }
-
+        self.m.apply(input, output)
     }
 
     fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
-        let mut state = vec![vec![F128::zero(); 1600]; 3];
-        for i in 0..5 {
-            for j in 0..3 {
-                state[j][i * 320 .. (i + 1) * 320].copy_from_slice(
-                    &input[i * 1024 + j * 320 .. i * 1024 + (j + 1) * 320]
-                );
-            }
-        }
-
-        let mut output_state = vec![vec![F128::zero(); 1600]; 3];
-
-        for j in 0..3 {
-            self.m.apply_transposed(&state[j], &mut output_state[j]);
-        }
-
-        for i in 0..5 {
-            for j in 0..3 {
-                output[i * 1024 + j * 320 .. i * 1024 + (j + 1) * 320].copy_from_slice(
-                    &output_state[j][i * 320 .. (i + 1) * 320]
-                );
-            }
-        }
-
+        self.m.apply_transposed(input, output)
     }
 }
 
@@ -346,7 +376,7 @@ mod tests {
     use itertools::Itertools;
     use num_traits::Zero;
     use rand::rngs::OsRng;
-    use crate::{protocols::{lincheck::{Lincheck, LincheckOutput}, utils::{evaluate, evaluate_univar}}, traits::SumcheckObject};
+    use crate::{protocols::{lincheck::{Lincheck, LincheckOutput}, linop::check_adjoint, utils::{evaluate, evaluate_univar}}, traits::SumcheckObject};
 
     use super::*;
 
@@ -372,6 +402,38 @@ mod tests {
         assert!(lhs == rhs);
     }
 
+    #[test]
+    fn keccak_matrices_adjoint() {
+        let rng = &mut OsRng;
+        assert!(check_adjoint(&ThetaAC{}, rng, 1));
+        assert!(check_adjoint(&ThetaCD{}, rng, 1));
+        assert!(check_adjoint(&ThetaDE{}, rng, 1));
+        assert!(check_adjoint(&ThetaMatrix::new(), rng, 1));
+        assert!(check_adjoint(&RhoPiMatrix{}, rng, 1));
+        assert!(check_adjoint(&KeccakLinMatrixUnbatched::new(), rng, 1));
+        assert!(check_adjoint(&KeccakLinMatrix::new(), rng, 1));
+    }
+
+    #[test]
+    fn keccak_network_matches_handwritten() {
+        let rng = &mut OsRng;
+        let handwritten = Composition::new(RhoPiMatrix{}, ThetaMatrix::new());
+        let network = keccak_linear_network();
+
+        let a : Vec<_> = (0..1600).map(|_| F128::rand(rng)).collect();
+        let mut lhs = vec![F128::zero(); 1600];
+        let mut rhs = vec![F128::zero(); 1600];
+        handwritten.apply(&a, &mut lhs);
+        network.apply(&a, &mut rhs);
+        assert!(lhs == rhs);
+
+        let mut lhs = vec![F128::zero(); 1600];
+        let mut rhs = vec![F128::zero(); 1600];
+        handwritten.apply_transposed(&a, &mut lhs);
+        network.apply_transposed(&a, &mut rhs);
+        assert!(lhs == rhs);
+    }
+
     #[test]
 
     fn keccak_lincheck_ok() {
@@ -1,3 +1,4 @@
 pub mod chi_round;
 pub mod matrices;
-pub mod main_protocol;
\ No newline at end of file
+pub mod main_protocol;
+pub mod permutation;
\ No newline at end of file
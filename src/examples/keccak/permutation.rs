@@ -0,0 +1,187 @@
+// End-to-end prover of Keccak-f[1600] permutation: all 24 rounds, proven as a single layered circuit.
+
+// Every round consists of two layers: the linear layer (theta, rho and pi, see matrices::KeccakLinMatrix) and the
+// chi layer, followed by iota (ChiIotaPackage, proven with BoolCheck). The round constant of iota is the public
+// constant term of the formula, so it is evaluated by the verifier and requires no proof.
+// Output of the chi layer has the same layout as the input of the linear layer, so the rounds are wired together
+// directly, and the claim about the output state is reduced to the claim about the input state.
+
+// States are bit-sliced: the element (x, y, z) of j-th state of the batch holds the bit z of the lane (x, y) of
+// 128 different instances, see pack_states. Hence one batch of size 1024 holds 3 * 128 instances.
+
+// Here inputs and outputs are public, so the verifier evaluates them itself. If the input is committed, the input
+// claim must be opened instead (see main_protocol).
+
+use num_traits::Zero;
+
+use crate::{field::F128, hash::KECCAK_ROUND_CONSTANTS, protocols::{layered::{LayeredCircuit, LayeredProof}, pipeline::EvalClaim, utils::evaluate}, transcript::Transcript};
+
+use super::{chi_round::ChiIotaPackage, matrices::{position, KeccakLinMatrix, BATCH_VARS, STATES_PER_BATCH}};
+
+/// Number of instances of the permutation that fit into polynomials of num_vars variables.
+pub fn num_instances(num_vars: usize) -> usize {
+    assert!(num_vars >= BATCH_VARS);
+    (STATES_PER_BATCH * 128) << (num_vars - BATCH_VARS)
+}
+
+/// Packs states into 5 polynomials (one for each x). Missing instances are filled with zero states.
+pub fn pack_states(states: &[[u64; 25]], num_vars: usize) -> [Vec<F128>; 5] {
+    assert!(states.len() <= num_instances(num_vars));
+    let mut ret : [Vec<F128>; 5] = std::array::from_fn(|_| vec![F128::zero(); 1 << num_vars]);
+    for (s, state) in states.iter().enumerate() {
+        let (batch, j, t) = (s / (STATES_PER_BATCH * 128), (s / 128) % STATES_PER_BATCH, s % 128);
+        for x in 0..5 {
+            for y in 0..5 {
+                let lane = state[x + 5 * y];
+                for z in 0..64 {
+                    let bit = ((lane >> z) & 1) as u128;
+                    let v = &mut ret[x][position(batch, j, y, z)];
+                    *v = F128::from_raw(v.raw() | (bit << t));
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Inverse of pack_states, returns all num_instances(num_vars) states.
+pub fn unpack_states(polys: &[Vec<F128>; 5], num_vars: usize) -> Vec<[u64; 25]> {
+    (0..num_instances(num_vars)).map(|s| {
+        let (batch, j, t) = (s / (STATES_PER_BATCH * 128), (s / 128) % STATES_PER_BATCH, s % 128);
+        let mut state = [0u64; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..64 {
+                    let bit = (polys[x][position(batch, j, y, z)].raw() >> t) & 1;
+                    state[x + 5 * y] |= (bit as u64) << z;
+                }
+            }
+        }
+        state
+    }).collect()
+}
+
+pub struct KeccakPermutation {
+    circuit: LayeredCircuit<5, KeccakLinMatrix, ChiIotaPackage>,
+}
+
+impl KeccakPermutation {
+    /// c is a phase switch parameter of BoolCheck.
+    pub fn new(num_vars: usize, c: usize) -> Self {
+        assert!(num_vars >= BATCH_VARS);
+        let mut circuit = LayeredCircuit::new(num_vars);
+        for round in 0..KECCAK_ROUND_CONSTANTS.len() {
+            circuit = circuit
+                .linear(KeccakLinMatrix::new(), BATCH_VARS)
+                .quadratic(ChiIotaPackage::new(round), c);
+        }
+        Self { circuit }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.circuit.num_vars()
+    }
+
+    /// Absorbs the input and the output, and samples the point in which the output is evaluated.
+    fn output_point(&self, input: &[Vec<F128>; 5], output: &[Vec<F128>; 5], transcript: &mut Transcript) -> Vec<F128> {
+        for poly in input.iter().chain(output.iter()) {
+            transcript.absorb(poly);
+        }
+        transcript.challenges(self.num_vars())
+    }
+
+    /// Computes the permutation of all states, and proves it. Returns the output states (all num_instances of
+    /// them, including the padding) and the proof.
+    pub fn prove(&self, states: &[[u64; 25]], transcript: &mut Transcript) -> (Vec<[u64; 25]>, LayeredProof) {
+        let num_vars = self.num_vars();
+        let wtns = self.circuit.witness(pack_states(states, num_vars));
+        let (input, output) = (&wtns[0], wtns.last().unwrap());
+
+        let point = self.output_point(input, output, transcript);
+        let values = output.iter().map(|poly| evaluate(poly, &point)).collect();
+        let (proof, _) = self.circuit.prove(&wtns, EvalClaim { point, values }, transcript);
+
+        (unpack_states(output, num_vars), proof)
+    }
+
+    /// Checks that outputs are the images of inputs under the permutation.
+    pub fn verify(&self, inputs: &[[u64; 25]], outputs: &[[u64; 25]], proof: &LayeredProof, transcript: &mut Transcript) -> bool {
+        let num_vars = self.num_vars();
+        if inputs.len() > num_instances(num_vars) || outputs.len() != num_instances(num_vars) {
+            return false;
+        }
+        let input = pack_states(inputs, num_vars);
+        let output = pack_states(outputs, num_vars);
+
+        let point = self.output_point(&input, &output, transcript);
+        let values = output.iter().map(|poly| evaluate(poly, &point)).collect();
+        let Some(EvalClaim { point, values }) = self.circuit.verify(EvalClaim { point, values }, proof, transcript) else {
+            return false;
+        };
+        input.iter().zip(values.iter()).all(|(poly, value)| evaluate(poly, &point) == *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::{rngs::OsRng, Rng};
+
+    use crate::{examples::keccak::matrices::keccak_linround_witness, hash::{keccak_f1600, keccak_round}, protocols::layered::quadratic_layer_witness};
+
+    use super::*;
+
+    #[test]
+    fn packing_roundtrip() {
+        let rng = &mut OsRng;
+        let num_vars = 11;
+        let states : Vec<[u64; 25]> = (0..num_instances(num_vars)).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+        assert!(unpack_states(&pack_states(&states, num_vars), num_vars) == states);
+    }
+
+    #[test]
+    fn single_round_matches_reference() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+        let states : Vec<[u64; 25]> = (0..num_instances(num_vars)).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+
+        let polys = pack_states(&states, num_vars);
+        let lin = keccak_linround_witness(std::array::from_fn(|i| polys[i].as_slice()));
+        let out = quadratic_layer_witness(&lin, &ChiIotaPackage::new(7));
+
+        for (state, result) in states.iter().zip(unpack_states(&out, num_vars)) {
+            let mut expected = *state;
+            keccak_round(&mut expected, KECCAK_ROUND_CONSTANTS[7]);
+            assert!(expected == result);
+        }
+    }
+
+    #[test]
+    fn keccak_permutation_works() {
+        let rng = &mut OsRng;
+        let num_vars = 11;
+        let keccak = KeccakPermutation::new(num_vars, 5);
+
+        // Not all slots are used, the rest is padded with zero states.
+        let inputs : Vec<[u64; 25]> = (0..num_instances(num_vars) - 100).map(|_| std::array::from_fn(|_| rng.gen())).collect();
+
+        let label = Instant::now();
+        let (outputs, proof) = keccak.prove(&inputs, &mut Transcript::new(b"keccak"));
+        println!("Proof of {} permutations took {} ms", inputs.len(), label.elapsed().as_millis());
+
+        for (input, output) in inputs.iter().zip(outputs.iter()) {
+            let mut expected = *input;
+            keccak_f1600(&mut expected);
+            assert!(expected == *output);
+        }
+
+        let label = Instant::now();
+        assert!(keccak.verify(&inputs, &outputs, &proof, &mut Transcript::new(b"keccak")));
+        println!("Verification took {} ms", label.elapsed().as_millis());
+
+        let mut wrong_outputs = outputs.clone();
+        wrong_outputs[5][0] ^= 1;
+        assert!(!keccak.verify(&inputs, &wrong_outputs, &proof, &mut Transcript::new(b"keccak")));
+    }
+}
@@ -58,6 +58,18 @@ impl F128 {
     pub fn cobasis(i: usize) -> Self {
         Self::from_raw(COBASIS[i])
     }
+
+    /// Multiplicative inverse, computed as x^(2^128 - 2). Panics on zero.
+    pub fn inv(&self) -> Self {
+        assert!(!self.is_zero(), "Zero is not invertible.");
+        let mut x = *self;
+        let mut ret = Self::one();
+        for _ in 1..128 {
+            x = x * x;
+            ret *= x;
+        }
+        ret
+    }
 }
 
 impl Zero for F128 {
@@ -281,6 +293,16 @@ mod tests {
         assert_eq!(a, x);
     }
 
+    #[test]
+    fn inv_works() {
+        let rng = &mut OsRng;
+        let a = F128::rand(rng);
+        let one = F128::one();
+
+        assert_eq!(a * a.inv(), one);
+        assert_eq!(one.inv(), one);
+    }
+
     #[test]
     fn frobenius() {
         let rng = &mut OsRng;
@@ -0,0 +1,129 @@
+// Native implementation of Keccak-f[1600] permutation and Keccak-256 sponge. This is used for vector commitments
+// and Fiat-Shamir challenges, and also serves as a reference for the Keccak example.
+
+use bytemuck::cast_slice;
+
+use crate::field::F128;
+
+pub const KECCAK_ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808A, 0x8000000080008000,
+    0x000000000000808B, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008A, 0x0000000000000088, 0x0000000080008009, 0x000000008000000A,
+    0x000000008000808B, 0x800000000000008B, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800A, 0x800000008000000A,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// Rotation offsets of rho step, indexed by [x][y].
+pub const KECCAK_RHO_OFFSETS: [[u32; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+/// A single round of Keccak-f[1600]. Lane (x, y) is stored in state[x + 5 * y].
+pub fn keccak_round(state: &mut [u64; 25], round_constant: u64) {
+    // theta
+    let mut c = [0u64; 5];
+    for x in 0..5 {
+        c[x] = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+    }
+    for x in 0..5 {
+        let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+        for y in 0..5 {
+            state[x + 5 * y] ^= d;
+        }
+    }
+    // rho and pi
+    let mut b = [0u64; 25];
+    for x in 0..5 {
+        for y in 0..5 {
+            b[y + 5 * ((2 * x + 3 * y) % 5)] = state[x + 5 * y].rotate_left(KECCAK_RHO_OFFSETS[x][y]);
+        }
+    }
+    // chi
+    for x in 0..5 {
+        for y in 0..5 {
+            state[x + 5 * y] = b[x + 5 * y] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+        }
+    }
+    // iota
+    state[0] ^= round_constant;
+}
+
+pub fn keccak_f1600(state: &mut [u64; 25]) {
+    for rc in KECCAK_ROUND_CONSTANTS {
+        keccak_round(state, rc);
+    }
+}
+
+const KECCAK256_RATE: usize = 136;
+
+/// Keccak-256 (with the original Keccak padding, as used in Ethereum).
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    let mut chunks = data.chunks_exact(KECCAK256_RATE);
+    for chunk in &mut chunks {
+        absorb_block(&mut state, chunk);
+        keccak_f1600(&mut state);
+    }
+    let rem = chunks.remainder();
+    let mut last = [0u8; KECCAK256_RATE];
+    last[..rem.len()].copy_from_slice(rem);
+    last[rem.len()] ^= 0x01;
+    last[KECCAK256_RATE - 1] ^= 0x80;
+    absorb_block(&mut state, &last);
+    keccak_f1600(&mut state);
+
+    let mut ret = [0u8; 32];
+    for i in 0..4 {
+        ret[8 * i .. 8 * i + 8].copy_from_slice(&state[i].to_le_bytes());
+    }
+    ret
+}
+
+fn absorb_block(state: &mut [u64; 25], block: &[u8]) {
+    for i in 0 .. KECCAK256_RATE / 8 {
+        state[i] ^= u64::from_le_bytes(block[8 * i .. 8 * i + 8].try_into().unwrap());
+    }
+}
+
+pub fn keccak256_f128(data: &[F128]) -> [u8; 32] {
+    keccak256(cast_slice(data))
+}
+
+/// Derives a sequence of F128 challenges from a seed (Fiat-Shamir).
+pub fn challenges_from_seed(seed: &[u8; 32], domain: u8, n: usize) -> Vec<F128> {
+    (0..n).map(|i| {
+        let mut input = seed.to_vec();
+        input.push(domain);
+        input.extend_from_slice(&(i as u64).to_le_bytes());
+        let h = keccak256(&input);
+        F128::from_raw(u128::from_le_bytes(h[..16].try_into().unwrap()))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn keccak256_test_vectors() {
+        assert_eq!(hex(&keccak256(b"")), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        assert_eq!(hex(&keccak256(b"abc")), "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+    }
+
+    #[test]
+    fn keccak_f1600_zero_state() {
+        let mut state = [0u64; 25];
+        keccak_f1600(&mut state);
+        assert_eq!(state[0], 0xF1258F7940E1DDE7);
+        assert_eq!(state[1], 0x84D5CCF933C0478A);
+    }
+}
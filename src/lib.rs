@@ -6,5 +6,10 @@ pub mod ptr_utils;
 pub mod precompute;
 pub mod backend;
 pub mod traits;
+pub mod hash;
+pub mod merkle;
+pub mod transcript;
+pub mod ntt;
+pub mod commitment;
 pub mod protocols;
 pub mod examples;
\ No newline at end of file
@@ -0,0 +1,234 @@
+// Merkle tree over rows of F128 values, with pluggable hash function.
+
+use std::{collections::BTreeMap, marker::PhantomData};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use bytemuck::cast_slice;
+
+use crate::{field::F128, hash::keccak256};
+
+pub type Digest = [u8; 32];
+
+/// Hash function used by the Merkle tree. Leaves and internal nodes must be hashed in different domains, otherwise
+/// a row could be passed off as a pair of child digests (and vice versa).
+pub trait MerkleHasher {
+    fn hash_leaf(row: &[F128]) -> Digest;
+    fn hash_node(left: &Digest, right: &Digest) -> Digest;
+}
+
+/// Default hasher, using native Keccak-256 (see hash.rs, which is also the reference permutation for the
+/// Keccak example). Leaf hashes are prefixed with LEAF_PREFIX, node hashes with NODE_PREFIX.
+pub struct KeccakHasher;
+
+pub const LEAF_PREFIX: u8 = 0x00;
+pub const NODE_PREFIX: u8 = 0x01;
+
+impl MerkleHasher for KeccakHasher {
+    fn hash_leaf(row: &[F128]) -> Digest {
+        let mut input = Vec::with_capacity(1 + 16 * row.len());
+        input.push(LEAF_PREFIX);
+        input.extend_from_slice(cast_slice(row));
+        keccak256(&input)
+    }
+
+    fn hash_node(left: &Digest, right: &Digest) -> Digest {
+        let mut input = [0u8; 65];
+        input[0] = NODE_PREFIX;
+        input[1..33].copy_from_slice(left);
+        input[33..].copy_from_slice(right);
+        keccak256(&input)
+    }
+}
+
+pub struct MerkleTree<H: MerkleHasher = KeccakHasher> {
+    /// layers[0] are hashes of the leaves, last layer is the root.
+    layers: Vec<Vec<Digest>>,
+    _marker: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    /// Commits to the rows, number of rows must be a power of 2.
+    pub fn new(rows: &[Vec<F128>]) -> Self {
+        assert!(rows.len().is_power_of_two());
+
+        #[cfg(not(feature = "parallel"))]
+        let leaves = rows.iter().map(|row| H::hash_leaf(row)).collect::<Vec<_>>();
+        #[cfg(feature = "parallel")]
+        let leaves = rows.par_iter().map(|row| H::hash_leaf(row)).collect::<Vec<_>>();
+
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers.last().unwrap().chunks(2).map(|pair| H::hash_node(&pair[0], &pair[1])).collect();
+            layers.push(next);
+        }
+        Self { layers, _marker: PhantomData }
+    }
+
+    pub fn root(&self) -> Digest {
+        self.layers.last().unwrap()[0]
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    /// Authentication path of the i-th row, from the leaf level up.
+    pub fn open(&self, mut i: usize) -> Vec<Digest> {
+        assert!(i < self.num_leaves());
+        let mut path = Vec::with_capacity(self.depth());
+        for layer in &self.layers[..self.depth()] {
+            path.push(layer[i ^ 1]);
+            i >>= 1;
+        }
+        path
+    }
+
+    /// Batched opening of several rows. Only sibling hashes which can not be computed from the opened rows are
+    /// included, layer by layer from the leaves, and in increasing order of indices inside of a layer.
+    /// Indices can be in any order and can repeat.
+    pub fn open_batch(&self, indices: &[usize]) -> Vec<Digest> {
+        let mut known : Vec<usize> = indices.to_vec();
+        known.sort();
+        known.dedup();
+        assert!(known.last().map_or(true, |&i| i < self.num_leaves()));
+
+        let mut proof = vec![];
+        for layer in &self.layers[..self.depth()] {
+            for (k, &i) in known.iter().enumerate() {
+                let sibling_known = if i & 1 == 0 {
+                    known.get(k + 1) == Some(&(i ^ 1))
+                } else {
+                    k > 0 && known[k - 1] == i ^ 1
+                };
+                if !sibling_known {
+                    proof.push(layer[i ^ 1]);
+                }
+            }
+            known = known.iter().map(|i| i >> 1).collect();
+            known.dedup();
+        }
+        proof
+    }
+}
+
+pub fn verify_path<H: MerkleHasher>(root: &Digest, mut i: usize, row: &[F128], path: &[Digest]) -> bool {
+    let mut h = H::hash_leaf(row);
+    for sibling in path {
+        h = if i & 1 == 0 { H::hash_node(&h, sibling) } else { H::hash_node(sibling, &h) };
+        i >>= 1;
+    }
+    i == 0 && h == *root
+}
+
+/// Verifies batched opening of rows[k] at indices[k], in a tree of a given depth.
+pub fn verify_batch<H: MerkleHasher>(root: &Digest, depth: usize, indices: &[usize], rows: &[Vec<F128>], proof: &[Digest]) -> bool {
+    if indices.len() != rows.len() || indices.len() == 0 {
+        return false;
+    }
+    let mut known = BTreeMap::new();
+    for (&i, row) in indices.iter().zip(rows.iter()) {
+        if i >= 1 << depth {
+            return false;
+        }
+        let h = H::hash_leaf(row);
+        // Repeated index must be opened to the same row.
+        if *known.entry(i).or_insert(h) != h {
+            return false;
+        }
+    }
+
+    let mut proof = proof.iter();
+    for _ in 0..depth {
+        let mut next = BTreeMap::new();
+        let mut iter = known.iter().peekable();
+        while let Some((&i, h)) = iter.next() {
+            let (left, right) = if i & 1 == 0 {
+                match iter.peek() {
+                    Some((&j, h_sibling)) if j == i ^ 1 => {
+                        let h_sibling = **h_sibling;
+                        iter.next();
+                        (*h, h_sibling)
+                    },
+                    _ => match proof.next() {
+                        Some(s) => (*h, *s),
+                        None => return false,
+                    },
+                }
+            } else {
+                match proof.next() {
+                    Some(s) => (*s, *h),
+                    None => return false,
+                }
+            };
+            next.insert(i >> 1, H::hash_node(&left, &right));
+        }
+        known = next;
+    }
+
+    proof.next().is_none() && known.get(&0) == Some(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::One;
+    use rand::{rngs::OsRng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn leaves_and_nodes_are_separated() {
+        let rng = &mut OsRng;
+        let children : Vec<F128> = (0..4).map(|_| F128::rand(rng)).collect();
+        let (left, right) : (Digest, Digest) = (cast_slice(&children[..2]).try_into().unwrap(), cast_slice(&children[2..]).try_into().unwrap());
+        // The row has the same bytes as the concatenation of the child digests.
+        assert!(KeccakHasher::hash_leaf(&children) != KeccakHasher::hash_node(&left, &right));
+    }
+
+    #[test]
+    fn merkle_paths_verify() {
+        let rng = &mut OsRng;
+        let rows : Vec<Vec<F128>> = (0..16).map(|_| (0..5).map(|_| F128::rand(rng)).collect()).collect();
+        let tree = MerkleTree::<KeccakHasher>::new(&rows);
+        let root = tree.root();
+        for i in 0..16 {
+            let path = tree.open(i);
+            assert!(verify_path::<KeccakHasher>(&root, i, &rows[i], &path));
+            assert!(!verify_path::<KeccakHasher>(&root, i ^ 1, &rows[i], &path));
+        }
+        let mut bad_row = rows[3].clone();
+        bad_row[0] += F128::one();
+        assert!(!verify_path::<KeccakHasher>(&root, 3, &bad_row, &tree.open(3)));
+    }
+
+    #[test]
+    fn merkle_batch_openings_verify() {
+        let rng = &mut OsRng;
+        let depth = 8;
+        let rows : Vec<Vec<F128>> = (0 .. 1 << depth).map(|_| (0..3).map(|_| F128::rand(rng)).collect()).collect();
+        let tree = MerkleTree::<KeccakHasher>::new(&rows);
+        let root = tree.root();
+
+        for num_queries in [1, 2, 7, 40, 300] {
+            let indices : Vec<usize> = (0..num_queries).map(|_| rng.gen_range(0 .. 1 << depth)).collect();
+            let opened : Vec<_> = indices.iter().map(|&i| rows[i].clone()).collect();
+            let proof = tree.open_batch(&indices);
+            assert!(proof.len() <= num_queries * depth);
+            assert!(verify_batch::<KeccakHasher>(&root, depth, &indices, &opened, &proof));
+
+            let mut bad = opened.clone();
+            bad[0][1] += F128::one();
+            assert!(!verify_batch::<KeccakHasher>(&root, depth, &indices, &bad, &proof));
+
+            let mut bad_proof = proof.clone();
+            if bad_proof.len() > 0 {
+                bad_proof.pop();
+                assert!(!verify_batch::<KeccakHasher>(&root, depth, &indices, &opened, &bad_proof));
+            }
+        }
+    }
+}
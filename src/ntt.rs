@@ -0,0 +1,244 @@
+// Additive NTT over F128 (Lin-Chung-Han), evaluating polynomials on affine F2-linear subspaces.
+
+// Subspace V_m is spanned by basis elements b_0, ..., b_{m-1}, where b_i = F128::basis(i), so that i-th point of
+// the subspace is F128::from_raw(i). Let W_i(x) = prod_{u in V_i} (x - u) be the subspace vanishing polynomials,
+// which are F2-linear, and Wh_i(x) = W_i(x) / W_i(b_i) their normalized versions.
+// Polynomials are represented in the novel basis X_k(x) = prod_{bits j of k} Wh_j(x), which has the same span
+// as monomials of degree < 2^m (so, in particular, it can be used for Reed-Solomon encoding).
+// Forward transform maps novel basis coefficients to evaluations in points shift + F128::from_raw(i).
+
+use num_traits::{One, Zero};
+use rayon::{iter::{IndexedParallelIterator, ParallelIterator}, slice::ParallelSliceMut};
+
+use crate::field::F128;
+
+pub struct AdditiveNtt {
+    log_size: usize,
+    normalized: Vec<[F128; 128]>, // normalized[i][l] = Wh_i(b_l)
+    twiddles: Vec<Vec<F128>>, // twiddles[i][j] = Wh_i of the first point of j-th block of size 2^{i+1}
+}
+
+impl AdditiveNtt {
+    /// Precomputes twiddles for transforms of size up to 2^log_size.
+    pub fn new(log_size: usize) -> Self {
+        assert!(log_size < 128);
+        let mut vals : [F128; 128] = std::array::from_fn(|l| F128::basis(l)); // W_i(b_l)
+        let mut normalized = Vec::with_capacity(log_size);
+        for i in 0..log_size {
+            let norm = vals[i];
+            let norm_inv = norm.inv();
+            normalized.push(std::array::from_fn(|l| vals[l] * norm_inv));
+            // W_{i+1}(x) = W_i(x) * W_i(x + b_i) = W_i(x) * (W_i(x) + W_i(b_i))
+            for l in 0..128 {
+                vals[l] = vals[l] * (vals[l] + norm);
+            }
+        }
+
+        let twiddles = (0..log_size).map(|i| {
+            let mut tw = vec![F128::zero(); 1 << (log_size - i - 1)];
+            for j in 1..tw.len() {
+                // Gray-code style: add contribution of the lowest set bit.
+                let b = j.trailing_zeros() as usize;
+                tw[j] = tw[j & (j - 1)] + normalized[i][i + 1 + b];
+            }
+            tw
+        }).collect();
+
+        Self { log_size, normalized, twiddles }
+    }
+
+    pub fn log_size(&self) -> usize {
+        self.log_size
+    }
+
+    /// Evaluates Wh_i(x), using F2-linearity.
+    pub fn normalized_vanishing(&self, i: usize, x: F128) -> F128 {
+        let raw = x.raw();
+        let mut ret = F128::zero();
+        for l in 0..128 {
+            if (raw >> l) & 1 == 1 {
+                ret += self.normalized[i][l];
+            }
+        }
+        ret
+    }
+
+    /// Transforms novel basis coefficients to evaluations in points shift + F128::from_raw(i), in place.
+    pub fn forward(&self, data: &mut [F128], shift: F128) {
+        let m = log2_len(data, self.log_size);
+        for i in (0..m).rev() {
+            self.layer(data, i, shift, |lo, hi, t| {
+                for (u0, u1) in lo.iter_mut().zip(hi.iter_mut()) {
+                    *u0 += *u1 * t;
+                    *u1 += *u0;
+                }
+            });
+        }
+    }
+
+    /// Inverse of forward.
+    pub fn inverse(&self, data: &mut [F128], shift: F128) {
+        let m = log2_len(data, self.log_size);
+        for i in 0..m {
+            self.layer(data, i, shift, |lo, hi, t| {
+                for (u0, u1) in lo.iter_mut().zip(hi.iter_mut()) {
+                    *u1 += *u0;
+                    *u0 += *u1 * t;
+                }
+            });
+        }
+    }
+
+    /// Applies butterflies of i-th layer to all blocks of size 2^{i+1}.
+    fn layer<F: Fn(&mut [F128], &mut [F128], F128) + Sync>(&self, data: &mut [F128], i: usize, shift: F128, butterfly: F) {
+        let shift_tw = self.normalized_vanishing(i, shift);
+        let twiddles = &self.twiddles[i];
+        let half = 1 << i;
+        let process = |(j, block): (usize, &mut [F128])| {
+            let (lo, hi) = block.split_at_mut(half);
+            butterfly(lo, hi, twiddles[j] + shift_tw);
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        data.chunks_mut(2 * half).enumerate().for_each(process);
+
+        #[cfg(feature = "parallel")]
+        if data.len() < NTT_PAR_THRESHOLD {
+            data.chunks_mut(2 * half).enumerate().for_each(process);
+        } else {
+            data.par_chunks_mut(2 * half).enumerate().for_each(process);
+        }
+    }
+
+    /// Reed-Solomon encoding: treats msg as novel basis coefficients, and evaluates it in points F128::from_raw(i)
+    /// for i < msg.len() << log_blowup. This is done by a separate forward transform on every coset of the subspace
+    /// of size msg.len().
+    pub fn encode(&self, msg: &[F128], log_blowup: usize) -> Vec<F128> {
+        let k = msg.len();
+        let mut ret = Vec::with_capacity(k << log_blowup);
+        for c in 0 .. 1 << log_blowup {
+            ret.extend_from_slice(msg);
+            self.forward(&mut ret[c * k ..], F128::from_raw((c * k) as u128));
+        }
+        ret
+    }
+
+    /// Converts novel basis coefficients to monomial coefficients. Not efficient.
+    pub fn novel_to_monomial(&self, coeffs: &[F128]) -> Vec<F128> {
+        let m = log2_len(coeffs, self.log_size);
+        // Monomial coefficients of Wh_j.
+        let mut w = vec![F128::zero(), F128::one()]; // W_0(x) = x
+        let mut wh = vec![];
+        for j in 0..m {
+            let w_bj = evaluate_poly(&w, F128::basis(j));
+            let norm_inv = w_bj.inv();
+            wh.push(w.iter().map(|c| *c * norm_inv).collect::<Vec<_>>());
+            // W_{j+1}(x) = W_j(x)^2 + W_j(b_j) W_j(x)
+            let mut next = vec![F128::zero(); 2 * w.len() - 1];
+            for (s, c) in w.iter().enumerate() {
+                next[2 * s] += *c * c;
+                next[s] += *c * w_bj;
+            }
+            w = next;
+        }
+
+        let mut ret = vec![F128::zero(); coeffs.len()];
+        for k in 0..coeffs.len() {
+            let mut x_k = vec![coeffs[k]];
+            for j in 0..m {
+                if (k >> j) & 1 == 1 {
+                    x_k = mul_poly(&x_k, &wh[j]);
+                }
+            }
+            for (s, c) in x_k.iter().enumerate() {
+                ret[s] += c;
+            }
+        }
+        ret
+    }
+}
+
+/// Transforms smaller than this are not parallelized, as rayon overhead dominates.
+const NTT_PAR_THRESHOLD: usize = 1 << 12;
+
+fn log2_len(data: &[F128], log_size: usize) -> usize {
+    assert!(data.len().is_power_of_two());
+    let m = data.len().trailing_zeros() as usize;
+    assert!(m <= log_size, "Transform size exceeds precomputed twiddles.");
+    m
+}
+
+fn evaluate_poly(poly: &[F128], at: F128) -> F128 {
+    poly.iter().rev().fold(F128::zero(), |acc, c| acc * at + c)
+}
+
+fn mul_poly(a: &[F128], b: &[F128]) -> Vec<F128> {
+    let mut ret = vec![F128::zero(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            ret[i + j] += *x * y;
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::protocols::utils::evaluate_univar;
+
+    use super::*;
+
+    #[test]
+    fn ntt_matches_naive_evaluation() {
+        let rng = &mut OsRng;
+        let ntt = AdditiveNtt::new(8);
+        for m in [0, 1, 3, 6] {
+            let coeffs : Vec<_> = (0 .. 1 << m).map(|_| F128::rand(rng)).collect();
+            let monomial = ntt.novel_to_monomial(&coeffs);
+            let shift = F128::rand(rng);
+
+            let mut evals = coeffs.clone();
+            ntt.forward(&mut evals, shift);
+            for u in 0 .. 1 << m {
+                assert_eq!(evals[u], evaluate_univar(&monomial, shift + F128::from_raw(u as u128)));
+            }
+
+            ntt.inverse(&mut evals, shift);
+            assert_eq!(evals, coeffs);
+        }
+    }
+
+    #[test]
+    fn large_ntt_matches_naive_evaluation() {
+        let rng = &mut OsRng;
+        let ntt = AdditiveNtt::new(14);
+        // Only first 64 coefficients are non-zero, so that conversion to monomial basis is cheap.
+        let mut coeffs = vec![F128::zero(); 1 << 14];
+        coeffs[..64].iter_mut().map(|c| *c = F128::rand(rng)).count();
+        let monomial = ntt.novel_to_monomial(&coeffs[..64]);
+        let shift = F128::rand(rng);
+
+        let mut evals = coeffs.clone();
+        ntt.forward(&mut evals, shift);
+        for u in (0 .. 1 << 14).step_by(997) {
+            assert_eq!(evals[u], evaluate_univar(&monomial, shift + F128::from_raw(u as u128)));
+        }
+        ntt.inverse(&mut evals, shift);
+        assert_eq!(evals, coeffs);
+    }
+
+    #[test]
+    fn encode_matches_naive_evaluation() {
+        let rng = &mut OsRng;
+        let ntt = AdditiveNtt::new(5);
+        let msg : Vec<_> = (0 .. 1 << 5).map(|_| F128::rand(rng)).collect();
+        let monomial = ntt.novel_to_monomial(&msg);
+        let codeword = ntt.encode(&msg, 2);
+        assert_eq!(codeword.len(), 1 << 7);
+        for i in 0 .. 1 << 7 {
+            assert_eq!(codeword[i], evaluate_univar(&monomial, F128::from_raw(i as u128)));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use num_traits::{One, Zero};
+
+use crate::{field::F128, traits::{CompressedPoly, SumcheckObject}};
+
+use super::utils::evaluate_univar;
+
+/// Combines several sumchecks, possibly with different numbers of variables, into a single one, driven by a shared
+/// sequence of challenges. Round messages are combined with powers of the folding challenge.
+/// Instance with m < n variables (n being the maximal number of variables) is padded as x_0 * ... * x_{n-m-1} * P(x_{n-m}, ...),
+/// i.e. during first n - m rounds its round message is claim * t (note that doubling the claim, which is a usual
+/// way of padding, does not work in characteristic 2). It then uses last m challenges as its own.
+/// Instances are borrowed mutably, so that they can be finished by the caller after the batched protocol ends.
+pub struct BatchedSumcheck<'a> {
+    instances: Vec<&'a mut dyn SumcheckObject>,
+    num_vars: Vec<usize>,
+    claims: Vec<F128>,
+}
+
+impl<'a> BatchedSumcheck<'a> {
+    pub fn new() -> Self {
+        Self { instances: vec![], num_vars: vec![], claims: vec![] }
+    }
+
+    /// Adds an instance with a given number of variables and initial claim, returns its index.
+    pub fn add_instance(&mut self, instance: &'a mut dyn SumcheckObject, num_vars: usize, claim: F128) -> usize {
+        assert!(!instance.is_reverse_order(), "Unsupported order.");
+        assert!(num_vars > 0);
+        self.instances.push(instance);
+        self.num_vars.push(num_vars);
+        self.claims.push(claim);
+        self.instances.len() - 1
+    }
+
+    pub fn folding_challenge(self, gamma: F128) -> BatchedSumcheckSingle<'a> {
+        let Self { instances, num_vars, claims } = self;
+        assert!(instances.len() > 0);
+        let coeffs = batching_coeffs(instances.len(), gamma);
+        let claim = coeffs.iter().zip(claims.iter()).fold(F128::zero(), |acc, (c, x)| acc + *c * x);
+        let max_num_vars = *num_vars.iter().max().unwrap();
+
+        BatchedSumcheckSingle {
+            instances,
+            num_vars,
+            claims,
+            padding: vec![F128::one(); coeffs.len()],
+            coeffs,
+            max_num_vars,
+            claim,
+            challenges: vec![],
+            cached_round_msg: None,
+        }
+    }
+}
+
+pub struct BatchedSumcheckSingle<'a> {
+    instances: Vec<&'a mut dyn SumcheckObject>,
+    num_vars: Vec<usize>,
+    claims: Vec<F128>,
+    coeffs: Vec<F128>,
+    padding: Vec<F128>, // Products of challenges of the padding rounds passed so far.
+    max_num_vars: usize,
+    pub claim: F128,
+    pub challenges: Vec<F128>,
+
+    cached_round_msg: Option<CompressedPoly>,
+}
+
+pub struct BatchedSumcheckOutput {
+    /// Final claims of the instances, w.r.t. their own challenges (i.e. without padding factors).
+    pub claims: Vec<F128>,
+}
+
+impl<'a> BatchedSumcheckSingle<'a> {
+    pub fn num_vars(&self) -> usize {
+        self.max_num_vars
+    }
+
+    pub fn curr_round(&self) -> usize {
+        self.challenges.len()
+    }
+
+    /// Challenges used by i-th instance.
+    pub fn instance_challenges(&self, i: usize) -> &[F128] {
+        let skip = self.max_num_vars - self.num_vars[i];
+        &self.challenges[skip.min(self.challenges.len())..]
+    }
+
+    pub fn finish(self) -> BatchedSumcheckOutput {
+        assert!(self.curr_round() == self.max_num_vars, "Protocol has not finished yet.");
+        BatchedSumcheckOutput { claims: self.claims }
+    }
+
+    fn is_padding_round(&self, i: usize) -> bool {
+        self.curr_round() < self.max_num_vars - self.num_vars[i]
+    }
+}
+
+impl<'a> SumcheckObject for BatchedSumcheckSingle<'a> {
+    fn is_reverse_order(&self) -> bool {
+        false
+    }
+
+    fn bind(&mut self, challenge: F128) {
+        assert!(self.curr_round() < self.max_num_vars, "Protocol has already finished.");
+        let round_poly = self.round_msg().coeffs(self.claim);
+        self.claim = evaluate_univar(&round_poly, challenge);
+
+        for i in 0..self.instances.len() {
+            if self.is_padding_round(i) {
+                self.padding[i] *= challenge;
+            } else {
+                let instance_poly = self.instances[i].round_msg().coeffs(self.claims[i]);
+                self.claims[i] = evaluate_univar(&instance_poly, challenge);
+                self.instances[i].bind(challenge);
+            }
+        }
+
+        self.challenges.push(challenge);
+        self.cached_round_msg = None;
+    }
+
+    fn round_msg(&mut self) -> CompressedPoly {
+        assert!(self.curr_round() < self.max_num_vars, "Protocol has already finished.");
+        if self.cached_round_msg.is_some() {
+            return self.cached_round_msg.as_ref().unwrap().clone()
+        }
+
+        let mut poly = vec![F128::zero(); 2];
+        for i in 0..self.instances.len() {
+            let instance_poly = if self.is_padding_round(i) {
+                vec![F128::zero(), self.claims[i]]
+            } else {
+                self.instances[i].round_msg().coeffs(self.claims[i])
+            };
+            if instance_poly.len() > poly.len() {
+                poly.resize(instance_poly.len(), F128::zero());
+            }
+            let multiplier = self.coeffs[i] * self.padding[i];
+            for (s, c) in instance_poly.iter().enumerate() {
+                poly[s] += multiplier * c;
+            }
+        }
+
+        let (ret, expected_claim) = CompressedPoly::compress(&poly);
+        assert!(expected_claim == self.claim, "Current round: {}", self.curr_round()); // sanity check
+
+        self.cached_round_msg = Some(ret.clone());
+        ret
+    }
+}
+
+/// Powers of gamma, used as coefficients of the instances.
+pub fn batching_coeffs(num_instances: usize, gamma: F128) -> Vec<F128> {
+    let mut coeffs = Vec::with_capacity(num_instances);
+    let mut c = F128::one();
+    for _ in 0..num_instances {
+        coeffs.push(c);
+        c *= gamma;
+    }
+    coeffs
+}
+
+/// Verifier side: computes the final claim of the batched sumcheck from the final claims of the instances.
+pub fn batched_final_claim(num_vars: &[usize], final_claims: &[F128], challenges: &[F128], gamma: F128) -> F128 {
+    assert!(num_vars.len() == final_claims.len());
+    let max_num_vars = challenges.len();
+    let coeffs = batching_coeffs(num_vars.len(), gamma);
+    (0..num_vars.len()).map(|i| {
+        assert!(num_vars[i] <= max_num_vars);
+        let padding = challenges[.. max_num_vars - num_vars[i]].iter().fold(F128::one(), |acc, x| acc * x);
+        coeffs[i] * padding * final_claims[i]
+    }).fold(F128::zero(), |a, b| a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::protocols::{composite::{CompositeSumcheck, CompositeSumcheckOutput}, prodcheck::{Prodcheck, ProdcheckOutput}, utils::evaluate};
+
+    use super::*;
+
+    #[test]
+    fn batched_sumcheck_works() {
+        let rng = &mut OsRng;
+        let n_prod = 9;
+        let n_comp = 6;
+        let n_max = 9;
+
+        let p : Vec<_> = (0 .. 1 << n_prod).map(|_| F128::rand(rng)).collect();
+        let q : Vec<_> = (0 .. 1 << n_prod).map(|_| F128::rand(rng)).collect();
+        let prod_claim = p.iter().zip(q.iter()).fold(F128::zero(), |acc, (a, b)| acc + *a * b);
+
+        let r : Vec<_> = (0 .. 1 << n_comp).map(|_| F128::rand(rng)).collect();
+        let composition = |args: &[F128]| args[0] * args[0] * args[0];
+        let comp_claim = r.iter().fold(F128::zero(), |acc, a| acc + *a * a * a);
+
+        let mut prodcheck = Prodcheck::new(vec![p.clone()], vec![q.clone()], prod_claim, true, false);
+        let mut composite = CompositeSumcheck::new(vec![r.clone()], composition, 3, comp_claim);
+
+        let gamma = F128::rand(rng);
+        let mut batch = BatchedSumcheck::new();
+        batch.add_instance(&mut composite, n_comp, comp_claim);
+        batch.add_instance(&mut prodcheck, n_prod, prod_claim);
+        let mut prover = batch.folding_challenge(gamma);
+
+        let mut claim = comp_claim + gamma * prod_claim;
+        for _ in 0..n_max {
+            let round_poly = prover.round_msg().coeffs(claim);
+            let challenge = F128::rand(rng);
+            claim = evaluate_univar(&round_poly, challenge);
+            prover.bind(challenge);
+        }
+        let challenges = prover.challenges.clone();
+        let comp_challenges = prover.instance_challenges(0).to_vec();
+        let BatchedSumcheckOutput { claims } = prover.finish();
+
+        let ProdcheckOutput { p_evs, q_evs } = prodcheck.finish();
+        let CompositeSumcheckOutput { evals } = composite.finish();
+
+        assert!(p_evs[0] == evaluate(&p, &challenges));
+        assert!(evals[0] == evaluate(&r, &comp_challenges));
+        assert!(claims[0] == composition(&evals));
+        assert!(claims[1] == p_evs[0] * q_evs[0]);
+
+        assert!(batched_final_claim(&[n_comp, n_prod], &[composition(&evals), p_evs[0] * q_evs[0]], &challenges, gamma) == claim);
+    }
+}
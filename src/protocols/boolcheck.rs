@@ -1,7 +1,7 @@
 use num_traits::{One, Zero};
 use rayon::{iter::{IntoParallelIterator, ParallelIterator}, slice::{ParallelSlice, ParallelSliceMut}};
 
-use crate::{field::F128, protocols::utils::{compute_trit_mappings, eq_ev, eq_poly_sequence, extend_n_tables, restrict, restrict_legacy, twist_evals}, ptr_utils::ConstPtr, traits::{CompressedPoly, SumcheckObject}};
+use crate::{field::F128, protocols::utils::{compute_trit_mappings, eq_ev, eq_poly_sequence, extend_n_tables, restrict, restrict_legacy, twist_evals, untwist_evals}, ptr_utils::ConstPtr, traits::{CompressedPoly, SumcheckObject}};
 
 use super::utils::evaluate_univar;
 
@@ -18,6 +18,51 @@ pub trait FnPackage<const N: usize, const M: usize> : Send + Sync {
     /// (2*start + 1), counting with offset. Then applies full formula twice - to the first
     /// array, and to the second array, and the quadratic part to the element-wise sum of these arrays.
     fn exec_alg(&self, data: &[F128], start: usize, offset: usize) -> [[F128; M]; 3];
+
+    /// Applies algebraic form of the formula to a single array of size (128 * N), i.e. to the evaluations of
+    /// coordinate polynomials (obtained by untwisting the output of BoolCheck). This is what the verifier needs.
+    fn exec_alg_at(&self, coord_evals: &[F128]) -> [F128; M] {
+        assert!(coord_evals.len() == 128 * N);
+        // exec_alg also reads the second array, shifted by 1, so we pad the data with one more element.
+        let mut data = Vec::with_capacity(128 * N + 1);
+        data.extend_from_slice(coord_evals);
+        data.push(F128::zero());
+        self.exec_alg(&data, 0, 1)[0]
+    }
+
+    /// Public constant term of the formula in position x of the hypercube (in compressed form). It may depend on
+    /// the position, but not on the arguments, and so it does not take part in the sumcheck.
+    fn exec_const_compressed(&self, _x: usize) -> [F128; M] {
+        [F128::zero(); M]
+    }
+
+    /// Evaluates multilinear extension of the constant term in the point pt. This is computed by the verifier, who
+    /// subtracts it from the initial claim.
+    fn const_ev(&self, _pt: &[F128]) -> [F128; M] {
+        [F128::zero(); M]
+    }
+}
+
+impl<const N: usize, const M: usize, F: FnPackage<N, M>> FnPackage<N, M> for &F {
+    fn exec_lin_compressed(&self, arg: [F128; N]) -> [F128; M] {
+        (**self).exec_lin_compressed(arg)
+    }
+
+    fn exec_quad_compressed(&self, arg: [F128; N]) -> [F128; M] {
+        (**self).exec_quad_compressed(arg)
+    }
+
+    fn exec_alg(&self, data: &[F128], start: usize, offset: usize) -> [[F128; M]; 3] {
+        (**self).exec_alg(data, start, offset)
+    }
+
+    fn exec_const_compressed(&self, x: usize) -> [F128; M] {
+        (**self).exec_const_compressed(x)
+    }
+
+    fn const_ev(&self, pt: &[F128]) -> [F128; M] {
+        (**self).const_ev(pt)
+    }
 }
 
 pub trait FnPackageFolded<const N: usize> : Send + Sync {
@@ -118,8 +163,8 @@ impl<
             tmp *= gamma;
         }
 
+        let evaluation_claim = boolcheck_initial_claim(&f, &pt, &evaluation_claims, gamma);
         let f_folded = FoldWrapper::new(f, &gammas);
-        let evaluation_claim = evaluate_univar(&evaluation_claims, gamma);
 
         BoolCheckSingle::new(
             f_folded,
@@ -159,6 +204,36 @@ pub struct BoolCheckOutput {
 //    cached_poly_coords: Vec<Vec<F128>>,
 }
 
+/// Computes the initial claim of the sumcheck from the claimed evaluations of the outputs, removing the constant
+/// term of the formula.
+pub fn boolcheck_initial_claim<const N: usize, const M: usize, F: FnPackage<N, M>>(
+    f: &F,
+    pt: &[F128],
+    evaluation_claims: &[F128],
+    gamma: F128,
+) -> F128 {
+    assert!(evaluation_claims.len() == M);
+    let const_evs = f.const_ev(pt);
+    let claims : Vec<_> = evaluation_claims.iter().zip(const_evs.iter()).map(|(x, y)| *x + y).collect();
+    evaluate_univar(&claims, gamma)
+}
+
+/// Verifier side: computes the expected final claim of BoolCheck from the (twisted) evaluations returned by the prover,
+/// the initial point pt, challenges rs and the folding challenge gamma.
+pub fn boolcheck_final_claim<const N: usize, const M: usize, F: FnPackage<N, M>>(
+    f: &F,
+    pt: &[F128],
+    rs: &[F128],
+    gamma: F128,
+    frob_evals: &[F128],
+) -> F128 {
+    assert!(frob_evals.len() == 128 * N);
+    let mut coord_evals = frob_evals.to_vec();
+    coord_evals.chunks_mut(128).map(|chunk| untwist_evals(chunk)).count();
+    let claimed_evs = f.exec_alg_at(&coord_evals);
+    evaluate_univar(&claimed_evs, gamma) * eq_ev(pt, rs)
+}
+
 impl<
     const N: usize,
     F: FnPackageFolded<N>,
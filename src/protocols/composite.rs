@@ -0,0 +1,250 @@
+use num_traits::{One, Zero};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{field::F128, traits::{CompressedPoly, SumcheckObject}, utils::log2_exact};
+
+use super::utils::{eq_ev, eq_poly_sequence, evaluate_univar, interpolate_univar};
+
+/// Generic sumcheck for sum_x g(P_1(x), ..., P_k(x)), or sum_x eq(pt, x) g(P_1(x), ..., P_k(x)) if constructed
+/// with new_with_eq. The composition g is an arbitrary function of declared degree, which is only accessed as
+/// a black box, so round messages are obtained by interpolation from degree + 1 evaluations.
+/// This is much slower than specialized sumchecks (Prodcheck, BoolCheck), and is intended for prototyping and
+/// for small auxiliary claims.
+pub struct CompositeSumcheck<F: Fn(&[F128]) -> F128 + Send + Sync> {
+    pub polys: Vec<Vec<F128>>,
+    pub claim: F128,
+    pub challenges: Vec<F128>,
+    composition: F,
+    degree: usize,
+    pt: Option<Vec<F128>>,
+    eq_sequence: Vec<Vec<F128>>, // Precomputed eqs of all slices pt[i..], empty if there is no eq factor.
+    num_vars: usize,
+
+    cached_round_msg: Option<CompressedPoly>,
+}
+
+pub struct CompositeSumcheckOutput {
+    pub evals: Vec<F128>,
+}
+
+impl<F: Fn(&[F128]) -> F128 + Send + Sync> CompositeSumcheck<F> {
+    pub fn new(polys: Vec<Vec<F128>>, composition: F, degree: usize, claim: F128) -> Self {
+        Self::new_inner(polys, composition, degree, None, claim)
+    }
+
+    pub fn new_with_eq(polys: Vec<Vec<F128>>, composition: F, degree: usize, pt: Vec<F128>, claim: F128) -> Self {
+        Self::new_inner(polys, composition, degree, Some(pt), claim)
+    }
+
+    fn new_inner(polys: Vec<Vec<F128>>, composition: F, degree: usize, pt: Option<Vec<F128>>, claim: F128) -> Self {
+        assert!(polys.len() > 0);
+        assert!(degree > 0);
+        let num_vars = log2_exact(polys[0].len());
+        assert!(num_vars > 0);
+        for poly in polys.iter() {
+            assert!(poly.len() == 1 << num_vars);
+        }
+        let eq_sequence = match &pt {
+            Some(pt) => {
+                assert!(pt.len() == num_vars);
+                eq_poly_sequence(&pt[1..])
+            },
+            None => vec![],
+        };
+
+        Self {
+            polys,
+            claim,
+            challenges: vec![],
+            composition,
+            degree,
+            pt,
+            eq_sequence,
+            num_vars,
+            cached_round_msg: None,
+        }
+    }
+
+    pub fn curr_round(&self) -> usize {
+        self.challenges.len()
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    /// Degree of round polynomials, which is one more than degree of composition if eq factor is present.
+    pub fn round_degree(&self) -> usize {
+        self.degree + self.pt.is_some() as usize
+    }
+
+    pub fn finish(self) -> CompositeSumcheckOutput {
+        assert!(self.curr_round() == self.num_vars, "Protocol has not finished yet.");
+        let evals = self.polys.iter().map(|poly| poly[0]).collect();
+        CompositeSumcheckOutput { evals }
+    }
+
+    /// Evaluation points of the round polynomial (before multiplication by eq factor).
+    fn eval_points(&self) -> Vec<F128> {
+        (0 .. self.degree as u128 + 1).map(|i| F128::from_raw(i)).collect()
+    }
+}
+
+impl<F: Fn(&[F128]) -> F128 + Send + Sync> SumcheckObject for CompositeSumcheck<F> {
+    fn is_reverse_order(&self) -> bool {
+        false
+    }
+
+    fn bind(&mut self, challenge: F128) {
+        assert!(self.curr_round() < self.num_vars, "Protocol has already finished.");
+        let half = self.polys[0].len() / 2;
+
+        let round_poly = self.round_msg().coeffs(self.claim);
+        self.claim = evaluate_univar(&round_poly, challenge);
+        self.challenges.push(challenge);
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            for poly in self.polys.iter_mut() {
+                for j in 0..half {
+                    poly[j] = poly[2 * j] + (poly[2 * j + 1] + poly[2 * j]) * challenge;
+                }
+                poly.truncate(half);
+            }
+        }
+
+        #[cfg(feature = "parallel")]
+        {
+            self.polys = self.polys.iter().map(|poly| {
+                (0..half).into_par_iter().map(|j| {
+                    poly[2 * j] + (poly[2 * j + 1] + poly[2 * j]) * challenge
+                }).collect()
+            }).collect();
+        }
+
+        self.cached_round_msg = None;
+    }
+
+    fn round_msg(&mut self) -> CompressedPoly {
+        let round = self.curr_round();
+        assert!(round < self.num_vars, "Protocol has already finished.");
+
+        if self.cached_round_msg.is_some() {
+            return self.cached_round_msg.as_ref().unwrap().clone()
+        }
+
+        let half = self.polys[0].len() / 2;
+        let k = self.polys.len();
+        let points = self.eval_points();
+        let eq_evs = self.pt.as_ref().map(|_| &self.eq_sequence[self.num_vars - round - 1]);
+
+        // Accumulates eq(pt_>, x') * g(P(t, x')) for all evaluation points t.
+        let accumulate = |acc: &mut Vec<F128>, args: &mut Vec<F128>, i: usize| {
+            let multiplier = eq_evs.map_or(F128::one(), |eq_evs| eq_evs[i]);
+            for (s, t) in points.iter().enumerate() {
+                for j in 0..k {
+                    let p0 = self.polys[j][2 * i];
+                    let p1 = self.polys[j][2 * i + 1];
+                    args[j] = p0 + (p0 + p1) * t;
+                }
+                acc[s] += (self.composition)(args) * multiplier;
+            }
+        };
+
+        let zero = || (vec![F128::zero(); points.len()], vec![F128::zero(); k]);
+
+        #[cfg(not(feature = "parallel"))]
+        let (evals, _) = (0 .. half).fold(zero(), |(mut acc, mut args), i| {
+            accumulate(&mut acc, &mut args, i);
+            (acc, args)
+        });
+
+        #[cfg(feature = "parallel")]
+        let (evals, _) = (0 .. half).into_par_iter().fold(zero, |(mut acc, mut args), i| {
+            accumulate(&mut acc, &mut args, i);
+            (acc, args)
+        }).reduce(zero, |(mut a, args), (b, _)| {
+            a.iter_mut().zip(b.iter()).map(|(a, b)| *a += b).count();
+            (a, args)
+        });
+
+        let poly_deg_d = interpolate_univar(&points, &evals);
+
+        let poly_final = match &self.pt {
+            None => poly_deg_d,
+            Some(pt) => {
+                let eq_y_multiplier = eq_ev(&self.challenges, &pt[..round]);
+                // eq(t, pt_r) = (1 + pt_r) + t
+                let eq_t = [pt[round] + F128::one(), F128::one()];
+                let mut poly_final = vec![F128::zero(); poly_deg_d.len() + 1];
+                for (s, c) in poly_deg_d.iter().enumerate() {
+                    let c = *c * eq_y_multiplier;
+                    poly_final[s] += eq_t[0] * c;
+                    poly_final[s + 1] += eq_t[1] * c;
+                }
+                poly_final
+            }
+        };
+
+        let (ret, expected_claim) = CompressedPoly::compress(&poly_final);
+        assert!(expected_claim == self.claim, "Current round: {}", round); // sanity check
+
+        self.cached_round_msg = Some(ret.clone());
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::protocols::utils::{eq_poly, evaluate};
+
+    use super::*;
+
+    fn composition(args: &[F128]) -> F128 {
+        args[0] * args[1] * args[2] + args[1] * args[1] + args[2]
+    }
+
+    #[test]
+    fn composite_sumcheck_works() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+
+        for with_eq in [false, true] {
+            let polys : Vec<Vec<F128>> = (0..3).map(|_| (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect()).collect();
+            let pt : Vec<_> = (0..num_vars).map(|_| F128::rand(rng)).collect();
+            let eq = eq_poly(&pt);
+
+            let mut claim = (0 .. 1 << num_vars).map(|i| {
+                let v = composition(&[polys[0][i], polys[1][i], polys[2][i]]);
+                if with_eq {v * eq[i]} else {v}
+            }).fold(F128::zero(), |a, b| a + b);
+
+            let mut prover = if with_eq {
+                CompositeSumcheck::new_with_eq(polys.clone(), composition, 3, pt.clone(), claim)
+            } else {
+                CompositeSumcheck::new(polys.clone(), composition, 3, claim)
+            };
+
+            for _ in 0..num_vars {
+                let round_poly = prover.round_msg().coeffs(claim);
+                assert!(round_poly.len() == prover.round_degree() + 1);
+                let challenge = F128::rand(rng);
+                claim = evaluate_univar(&round_poly, challenge);
+                prover.bind(challenge);
+            }
+
+            let challenges = prover.challenges.clone();
+            let CompositeSumcheckOutput { evals } = prover.finish();
+            for j in 0..3 {
+                assert!(evals[j] == evaluate(&polys[j], &challenges));
+            }
+            let mut expected_claim = composition(&evals);
+            if with_eq {
+                expected_claim *= eq_ev(&pt, &challenges);
+            }
+            assert!(expected_claim == claim);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// Grand-product argument, proving that the product of all values of a polynomial equals a given value.
+
+// The prover builds a binary tree of products: layer n consists of the values V_n, and
+// V_k(x) = V_{k+1}(x, 0) * V_{k+1}(x, 1), where the last variable selects the half of the array. Layer 0 is the
+// product itself.
+// A claim V_k(r) = v is reduced to a claim about V_{k+1} by the sumcheck
+// v = sum_x eq(r, x) V_{k+1}(x, 0) V_{k+1}(x, 1),
+// (ProductSumcheck of degree 3), after which the prover sends L = V_{k+1}(r', 0), R = V_{k+1}(r', 1), and the
+// verifier combines them into a single claim V_{k+1}(r', t) = L + t(L + R) for a random t.
+
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{field::F128, traits::CompressedPoly, transcript::{prove_sumcheck, verify_sumcheck, Transcript}, utils::log2_exact};
+
+use super::{pipeline::EvalClaim, productcheck::{ProductSumcheck, ProductSumcheckOutput, ProductTerm}, utils::{eq_ev, eq_poly}};
+
+pub struct GrandProduct {
+    layers: Vec<Vec<F128>>, // layers[k] has size 2^k.
+}
+
+#[derive(Clone, Debug)]
+pub struct GrandProductLayerProof {
+    pub round_msgs: Vec<CompressedPoly>,
+    pub left: F128,
+    pub right: F128,
+}
+
+/// Proofs of layers, starting from the root.
+#[derive(Clone, Debug)]
+pub struct GrandProductProof {
+    pub layers: Vec<GrandProductLayerProof>,
+}
+
+impl GrandProduct {
+    pub fn new(values: Vec<F128>) -> Self {
+        let num_vars = log2_exact(values.len());
+        let mut layers = vec![values];
+        for _ in 0..num_vars {
+            let prev = layers.last().unwrap();
+            let half = prev.len() / 2;
+
+            #[cfg(not(feature = "parallel"))]
+            let next = (0..half).map(|i| prev[i] * prev[i + half]).collect();
+            #[cfg(feature = "parallel")]
+            let next = (0..half).into_par_iter().map(|i| prev[i] * prev[i + half]).collect();
+
+            layers.push(next);
+        }
+        layers.reverse();
+        Self { layers }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn product(&self) -> F128 {
+        self.layers[0][0]
+    }
+
+    /// Proves the product. It is absorbed into the transcript. Returns the proof and the claim about the values.
+    pub fn prove(&self, transcript: &mut Transcript) -> (GrandProductProof, EvalClaim) {
+        transcript.absorb(&[self.product()]);
+        let mut point = vec![];
+        let mut value = self.product();
+        let mut layers = vec![];
+
+        for k in 0..self.num_vars() {
+            let next = &self.layers[k + 1];
+            let half = next.len() / 2;
+            let mut prover = ProductSumcheck::new(
+                vec![eq_poly(&point), next[..half].to_vec(), next[half..].to_vec()],
+                vec![ProductTerm::new(vec![0, 1, 2])],
+                value,
+                false,
+            );
+            let (round_msgs, rs) = prove_sumcheck(&mut prover, k, transcript);
+            let ProductSumcheckOutput { evals } = prover.finish();
+            let (left, right) = (evals[1], evals[2]);
+            transcript.absorb(&[left, right]);
+            let t = transcript.challenge();
+
+            point = rs;
+            point.push(t);
+            value = left + t * (left + right);
+            layers.push(GrandProductLayerProof { round_msgs, left, right });
+        }
+
+        (GrandProductProof { layers }, EvalClaim { point, values: vec![value] })
+    }
+}
+
+/// Verifies that the product of 2^num_vars values equals product. Returns the claim about the values, or None if
+/// verification fails.
+pub fn verify_grand_product(num_vars: usize, product: F128, proof: &GrandProductProof, transcript: &mut Transcript) -> Option<EvalClaim> {
+    if proof.layers.len() != num_vars {
+        return None;
+    }
+    transcript.absorb(&[product]);
+    let mut point = vec![];
+    let mut value = product;
+
+    for (k, layer) in proof.layers.iter().enumerate() {
+        let GrandProductLayerProof { round_msgs, left, right } = layer;
+        if round_msgs.len() != k {
+            return None;
+        }
+        let (final_claim, rs) = verify_sumcheck(value, round_msgs, 3, transcript)?;
+        if eq_ev(&point, &rs) * left * right != final_claim {
+            return None;
+        }
+        transcript.absorb(&[*left, *right]);
+        let t = transcript.challenge();
+
+        point = rs;
+        point.push(t);
+        value = *left + t * (*left + right);
+    }
+
+    Some(EvalClaim { point, values: vec![value] })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use num_traits::One;
+    use rand::rngs::OsRng;
+
+    use crate::protocols::utils::evaluate;
+
+    use super::*;
+
+    #[test]
+    fn grand_product_works() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+        let values : Vec<_> = repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect();
+
+        let gp = GrandProduct::new(values.clone());
+        assert!(gp.product() == values.iter().fold(F128::one(), |acc, x| acc * x));
+
+        let (proof, claim) = gp.prove(&mut Transcript::new(b"gp"));
+        let verified = verify_grand_product(num_vars, gp.product(), &proof, &mut Transcript::new(b"gp")).unwrap();
+        assert!(verified.point == claim.point && verified.values == claim.values);
+        assert!(evaluate(&values, &claim.point) == claim.values[0]);
+
+        assert!(verify_grand_product(num_vars, gp.product() + F128::one(), &proof, &mut Transcript::new(b"gp")).is_none());
+    }
+}
@@ -0,0 +1,372 @@
+// GKR-style prover for layered circuits, consisting of linear and quadratic layers acting on N polynomials.
+
+// A linear layer applies a matrix to chunks of size 2^a (given by the first a variables), and is proven using
+// Lincheck. A quadratic layer applies a boolean formula (FnPackage) pointwise, and is proven using BoolCheck, the
+// openings in the Frobenius orbit being reduced to a single opening using MulticlaimCheck. The formula may contain
+// a public constant term (see FnPackage::exec_const_compressed). A constant layer adds public polynomials. Neither
+// requires a proof: the verifier evaluates constants itself and subtracts them from the claim.
+// Keccak puts its round constants into the formula, so it does not use constant layers; they are kept public for
+// circuits that add a public polynomial between layers.
+// The claim about the output layer is reduced layer by layer to the claim about the input layer, which then needs
+// to be checked against the commitment of the input (this is left to the caller).
+
+// The protocol is made non-interactive by Fiat-Shamir, see transcript::Transcript. Every layer absorbs its claim
+// (the point and the values), so the transcript is bound to the output claim passed by the caller.
+
+use num_traits::Zero;
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{field::F128, traits::CompressedPoly, transcript::{prove_sumcheck, verify_sumcheck, Transcript}, utils::log2_exact};
+
+use super::{
+    boolcheck::{boolcheck_final_claim, boolcheck_initial_claim, BoolCheck, BoolCheckOutput, FnPackage},
+    lincheck::{lincheck_final_claim, LinOp, Lincheck, LincheckOutput},
+    multiclaim::{multiclaim_final_claim, MulticlaimCheck},
+    pipeline::EvalClaim,
+    utils::{evaluate, evaluate_univar},
+};
+
+pub enum Layer<const N: usize, L: LinOp, F> {
+    Linear { matrix: L, num_active_vars: usize },
+    /// c is a phase switch parameter of BoolCheck.
+    Quadratic { f: F, c: usize },
+    /// Public polynomials in first b variables, where 2^b is their length, repeated along the remaining ones.
+    Constant { constants: [Vec<F128>; N] },
+}
+
+pub enum LayerProof {
+    Linear {
+        round_msgs: Vec<CompressedPoly>,
+        p_evs: Vec<F128>,
+    },
+    Quadratic {
+        round_msgs: Vec<CompressedPoly>,
+        frob_evals: Vec<F128>,
+        multiclaim_round_msgs: Vec<CompressedPoly>,
+        evals: Vec<F128>,
+    },
+    Constant,
+}
+
+/// Proofs of layers, ordered from output to input.
+pub struct LayeredProof {
+    pub layers: Vec<LayerProof>,
+}
+
+/// Layers are stored in the order of application, i.e. from input to output.
+pub struct LayeredCircuit<const N: usize, L: LinOp, F: FnPackage<N, N>> {
+    num_vars: usize,
+    layers: Vec<Layer<N, L, F>>,
+}
+
+impl<const N: usize, L: LinOp + Sync, F: FnPackage<N, N>> LayeredCircuit<N, L, F> {
+    pub fn new(num_vars: usize) -> Self {
+        Self { num_vars, layers: vec![] }
+    }
+
+    pub fn linear(mut self, matrix: L, num_active_vars: usize) -> Self {
+        assert!(num_active_vars <= self.num_vars);
+        assert!(matrix.n_in() == N << num_active_vars && matrix.n_out() == N << num_active_vars);
+        self.layers.push(Layer::Linear { matrix, num_active_vars });
+        self
+    }
+
+    pub fn quadratic(mut self, f: F, c: usize) -> Self {
+        assert!(c < self.num_vars);
+        self.layers.push(Layer::Quadratic { f, c });
+        self
+    }
+
+    pub fn constant(mut self, constants: [Vec<F128>; N]) -> Self {
+        for poly in constants.iter() {
+            assert!(poly.len().is_power_of_two() && poly.len() <= 1 << self.num_vars);
+        }
+        self.layers.push(Layer::Constant { constants });
+        self
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    pub fn num_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Computes values of all layers, starting from the input. Returns num_layers + 1 arrays of polynomials.
+    pub fn witness(&self, input: [Vec<F128>; N]) -> Vec<[Vec<F128>; N]> {
+        for poly in input.iter() {
+            assert!(poly.len() == 1 << self.num_vars);
+        }
+        let mut ret = vec![input];
+        for layer in &self.layers {
+            let next = match layer {
+                Layer::Linear { matrix, num_active_vars } => linear_layer_witness(ret.last().unwrap(), matrix, *num_active_vars),
+                Layer::Quadratic { f, .. } => quadratic_layer_witness(ret.last().unwrap(), f),
+                Layer::Constant { constants } => constant_layer_witness(ret.last().unwrap(), constants),
+            };
+            ret.push(next);
+        }
+        ret
+    }
+
+    /// Reduces the claim about the output layer to the claim about the input layer.
+    /// wtns is the output of witness(...).
+    pub fn prove(&self, wtns: &[[Vec<F128>; N]], claim: EvalClaim, transcript: &mut Transcript) -> (LayeredProof, EvalClaim) {
+        assert!(wtns.len() == self.layers.len() + 1);
+        let mut claim = claim;
+        let mut layers = vec![];
+
+        for (layer, polys) in self.layers.iter().zip(wtns.iter()).rev() {
+            let EvalClaim { point, values } = claim;
+            assert!(point.len() == self.num_vars);
+            let values : [F128; N] = values.try_into().unwrap();
+            transcript.absorb(&point);
+            transcript.absorb(&values);
+            let gamma = transcript.challenge();
+
+            match layer {
+                Layer::Linear { matrix, num_active_vars } => {
+                    let mut prover = Lincheck::new_borrowed(polys, point, matrix, *num_active_vars, values).folding_challenge(gamma);
+                    let (round_msgs, _) = prove_sumcheck(&mut prover, *num_active_vars, transcript);
+                    let new_point = prover.output_points().pop().unwrap();
+                    let LincheckOutput { p_evs, .. } = prover.finish();
+                    transcript.absorb(&p_evs);
+
+                    claim = EvalClaim { point: new_point, values: p_evs.clone() };
+                    layers.push(LayerProof::Linear { round_msgs, p_evs });
+                },
+                Layer::Quadratic { f, c } => {
+                    let mut prover = BoolCheck::new(f, polys.clone(), *c, values, point).folding_challenge(gamma);
+                    let (round_msgs, rs) = prove_sumcheck(&mut prover, self.num_vars, transcript);
+                    let BoolCheckOutput { frob_evals, .. } = prover.finish();
+                    transcript.absorb(&frob_evals);
+
+                    let gamma = transcript.challenge();
+                    let mut prover = MulticlaimCheck::new(polys, rs, frob_evals.clone()).folding_challenge(gamma);
+                    let (multiclaim_round_msgs, rs) = prove_sumcheck(&mut prover, self.num_vars, transcript);
+                    let evals = prover.finish();
+                    transcript.absorb(&evals);
+
+                    claim = EvalClaim { point: rs, values: evals.clone() };
+                    layers.push(LayerProof::Quadratic { round_msgs, frob_evals, multiclaim_round_msgs, evals });
+                },
+                Layer::Constant { constants } => {
+                    claim = constant_layer_claim(constants, point, &values);
+                    layers.push(LayerProof::Constant);
+                },
+            }
+        }
+
+        (LayeredProof { layers }, claim)
+    }
+
+    /// Verifies the proof of the claim about the output layer. Returns the claim about the input layer, which
+    /// must be checked separately, or None if verification fails.
+    pub fn verify(&self, claim: EvalClaim, proof: &LayeredProof, transcript: &mut Transcript) -> Option<EvalClaim> {
+        if proof.layers.len() != self.layers.len() {
+            return None;
+        }
+        let mut claim = claim;
+
+        for (layer, layer_proof) in self.layers.iter().rev().zip(proof.layers.iter()) {
+            let EvalClaim { point, values } = claim;
+            if point.len() != self.num_vars || values.len() != N {
+                return None;
+            }
+            transcript.absorb(&point);
+            transcript.absorb(&values);
+            let gamma = transcript.challenge();
+
+            claim = match (layer, layer_proof) {
+                (Layer::Linear { matrix, num_active_vars }, LayerProof::Linear { round_msgs, p_evs }) => {
+                    if round_msgs.len() != *num_active_vars || p_evs.len() != N {
+                        return None;
+                    }
+                    let (final_claim, rs) = verify_sumcheck(evaluate_univar(&values, gamma), round_msgs, 2, transcript)?;
+                    let active_vars : Vec<_> = (0..*num_active_vars).collect();
+                    if lincheck_final_claim(matrix, &point, &active_vars, &rs, gamma, p_evs) != final_claim {
+                        return None;
+                    }
+                    transcript.absorb(p_evs);
+
+                    let new_point = rs.iter().chain(point[*num_active_vars..].iter()).copied().collect();
+                    EvalClaim { point: new_point, values: p_evs.clone() }
+                },
+                (Layer::Quadratic { f, .. }, LayerProof::Quadratic { round_msgs, frob_evals, multiclaim_round_msgs, evals }) => {
+                    if round_msgs.len() != self.num_vars || frob_evals.len() != 128 * N
+                        || multiclaim_round_msgs.len() != self.num_vars || evals.len() != N {
+                        return None;
+                    }
+                    let initial_claim = boolcheck_initial_claim(f, &point, &values, gamma);
+                    let (final_claim, rs) = verify_sumcheck(initial_claim, round_msgs, 3, transcript)?;
+                    if boolcheck_final_claim(f, &point, &rs, gamma, frob_evals) != final_claim {
+                        return None;
+                    }
+                    transcript.absorb(frob_evals);
+
+                    let gamma = transcript.challenge();
+                    let (final_claim, new_point) = verify_sumcheck(evaluate_univar(frob_evals, gamma), multiclaim_round_msgs, 2, transcript)?;
+                    if multiclaim_final_claim(&rs, &new_point, gamma, evals) != final_claim {
+                        return None;
+                    }
+                    transcript.absorb(evals);
+
+                    EvalClaim { point: new_point, values: evals.clone() }
+                },
+                (Layer::Constant { constants }, LayerProof::Constant) => constant_layer_claim(constants, point, &values),
+                _ => return None,
+            };
+        }
+
+        Some(claim)
+    }
+}
+
+/// Applies the matrix to every chunk of size 2^num_active_vars. The input of the matrix is the concatenation of
+/// the corresponding chunks of all polynomials.
+pub fn linear_layer_witness<const N: usize, L: LinOp + Sync>(polys: &[Vec<F128>; N], matrix: &L, num_active_vars: usize) -> [Vec<F128>; N] {
+    let chunk_size = 1 << num_active_vars;
+    let num_chunks = polys[0].len() / chunk_size;
+
+    let apply_chunk = |j: usize| {
+        let mut input = Vec::with_capacity(N * chunk_size);
+        for poly in polys.iter() {
+            input.extend_from_slice(&poly[j * chunk_size .. (j + 1) * chunk_size]);
+        }
+        let mut output = vec![F128::zero(); N * chunk_size];
+        matrix.apply(&input, &mut output);
+        output
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    let chunks : Vec<Vec<F128>> = (0..num_chunks).map(apply_chunk).collect();
+    #[cfg(feature = "parallel")]
+    let chunks : Vec<Vec<F128>> = (0..num_chunks).into_par_iter().map(apply_chunk).collect();
+
+    std::array::from_fn(|i| {
+        chunks.iter().flat_map(|chunk| chunk[i * chunk_size .. (i + 1) * chunk_size].iter().copied()).collect()
+    })
+}
+
+/// Adds public polynomials, repeating them along the variables they do not depend on.
+pub fn constant_layer_witness<const N: usize>(polys: &[Vec<F128>; N], constants: &[Vec<F128>; N]) -> [Vec<F128>; N] {
+    std::array::from_fn(|i| {
+        let mask = constants[i].len() - 1;
+
+        #[cfg(not(feature = "parallel"))]
+        let ret = (0..polys[i].len()).map(|x| polys[i][x] + constants[i][x & mask]).collect();
+        #[cfg(feature = "parallel")]
+        let ret = (0..polys[i].len()).into_par_iter().map(|x| polys[i][x] + constants[i][x & mask]).collect();
+
+        ret
+    })
+}
+
+/// Converts the claim about the output of the constant layer into the claim about its input. The extension of a
+/// constant, which does not depend on the last variables, is evaluated in the first coordinates of the point only.
+fn constant_layer_claim<const N: usize>(constants: &[Vec<F128>; N], point: Vec<F128>, values: &[F128]) -> EvalClaim {
+    let values = constants.iter().zip(values.iter()).map(|(poly, value)| {
+        *value + evaluate(poly, &point[..log2_exact(poly.len())])
+    }).collect();
+    EvalClaim { point, values }
+}
+
+/// Applies the formula pointwise, using its compressed (bitwise) form, and adds the constant term.
+pub fn quadratic_layer_witness<const N: usize, F: FnPackage<N, N>>(polys: &[Vec<F128>; N], f: &F) -> [Vec<F128>; N] {
+    let l = polys[0].len();
+
+    let apply_point = |x: usize| {
+        let arg : [F128; N] = std::array::from_fn(|i| polys[i][x]);
+        let lin = f.exec_lin_compressed(arg);
+        let quad = f.exec_quad_compressed(arg);
+        let cnst = f.exec_const_compressed(x);
+        std::array::from_fn::<F128, N, _>(|i| lin[i] + quad[i] + cnst[i])
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    let values : Vec<[F128; N]> = (0..l).map(apply_point).collect();
+    #[cfg(feature = "parallel")]
+    let values : Vec<[F128; N]> = (0..l).into_par_iter().map(apply_point).collect();
+
+    std::array::from_fn(|i| values.iter().map(|v| v[i]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use num_traits::One;
+    use rand::rngs::OsRng;
+
+    use crate::{examples::keccak::{chi_round::{chi_round_witness, ChiPackage}, matrices::{keccak_linround_witness, KeccakLinMatrix}}, protocols::utils::evaluate};
+
+    use super::*;
+
+    #[test]
+    fn layered_keccak_rounds() {
+        let rng = &mut OsRng;
+        let num_vars = 12;
+
+        let circuit = LayeredCircuit::<5, _, _>::new(num_vars)
+            .linear(KeccakLinMatrix::new(), 10)
+            .quadratic(ChiPackage {}, 5)
+            .linear(KeccakLinMatrix::new(), 10)
+            .quadratic(ChiPackage {}, 5);
+
+        let input : [Vec<F128>; 5] = std::array::from_fn(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect());
+        let wtns = circuit.witness(input);
+
+        let lin = keccak_linround_witness(std::array::from_fn(|i| wtns[0][i].as_slice()));
+        assert!(lin == wtns[1]);
+        assert!(chi_round_witness(&lin) == wtns[2]);
+
+        let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let values : Vec<_> = wtns[4].iter().map(|poly| evaluate(poly, &pt)).collect();
+        let output_claim = EvalClaim { point: pt, values };
+
+        let (mut proof, input_claim) = circuit.prove(&wtns, output_claim.clone(), &mut Transcript::new(b"layered"));
+
+        let verified_claim = circuit.verify(output_claim.clone(), &proof, &mut Transcript::new(b"layered")).unwrap();
+        assert!(verified_claim.point == input_claim.point && verified_claim.values == input_claim.values);
+        for (poly, value) in wtns[0].iter().zip(input_claim.values.iter()) {
+            assert!(evaluate(poly, &input_claim.point) == *value);
+        }
+
+        if let LayerProof::Quadratic { evals, .. } = &mut proof.layers[2] {
+            evals[0] += F128::one();
+        }
+        assert!(circuit.verify(output_claim, &proof, &mut Transcript::new(b"layered")).is_none());
+    }
+
+    #[test]
+    fn constant_layer_works() {
+        let rng = &mut OsRng;
+        let num_vars = 8;
+
+        let constants : [Vec<F128>; 5] = std::array::from_fn(|_| repeat_with(|| F128::rand(rng)).take(1 << 3).collect());
+        let circuit = LayeredCircuit::<5, KeccakLinMatrix, _>::new(num_vars)
+            .quadratic(ChiPackage {}, 3)
+            .constant(constants.clone());
+
+        let input : [Vec<F128>; 5] = std::array::from_fn(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect());
+        let wtns = circuit.witness(input);
+        for i in 0..5 {
+            for x in 0 .. 1 << num_vars {
+                assert!(wtns[2][i][x] == wtns[1][i][x] + constants[i][x % 8]);
+            }
+        }
+
+        let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let values : Vec<_> = wtns[2].iter().map(|poly| evaluate(poly, &pt)).collect();
+        let output_claim = EvalClaim { point: pt, values };
+
+        let (proof, input_claim) = circuit.prove(&wtns, output_claim.clone(), &mut Transcript::new(b"layered"));
+        let verified_claim = circuit.verify(output_claim, &proof, &mut Transcript::new(b"layered")).unwrap();
+        assert!(verified_claim.point == input_claim.point && verified_claim.values == input_claim.values);
+        for (poly, value) in wtns[0].iter().zip(input_claim.values.iter()) {
+            assert!(evaluate(poly, &input_claim.point) == *value);
+        }
+    }
+}
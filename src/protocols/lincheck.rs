@@ -1,213 +1,400 @@
+use std::borrow::Cow;
 use std::time::Instant;
 
 use num_traits::{One, Zero};
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
 use rayon::slice::ParallelSlice;
 
-use crate::protocols::utils::{evaluate, evaluate_univar};
+use crate::protocols::utils::{evaluate, evaluate_univar, restrict_high};
 use crate::traits::{CompressedPoly, SumcheckObject};
-use crate::{field::F128, protocols::utils::eq_poly,};
+use crate::{field::F128, protocols::utils::eq_poly, utils::log2_exact};
 
 use super::prodcheck::{Prodcheck, ProdcheckOutput};
 
-pub trait LinOp {
-    fn n_in(&self) -> usize;
-    fn n_out(&self) -> usize;
-    
-    /// expects input of size n_in and output of size n_out
-    /// adds result to already existing output using +=
-    fn apply(&self, input: &[F128], output: &mut [F128]);
-    /// expects input of size n_out and output of size n_in
-    /// adds result to already existing output using +=
-    fn apply_transposed(&self, input: &[F128], output: &mut [F128]);
-}
+pub use super::linop::{Composition, IdentityMatrix, LinOp, MatrixSum};
 
-pub struct Composition<A: LinOp, B: LinOp> {
-    a: A,
-    b: B,
+/// Represents a linear sumcheck of the form
+/// M(pt_active; x_active) * P(x_active, pt_dormant),
+/// where x_active are "active" variables, and the remaining ones are "dormant" and fixed to the
+/// corresponding coordinates of pt.
+/// By default, active variables are x_0, ..., x_{a-1}, but any subset (in any order) can be chosen
+/// using `with_active_vars`. This allows to prove matrices acting along any axis of a multidimensional
+/// trace without transposing the witness.
+/// It is very small, and the main computational work is computing the restriction
+/// P(x_active, pt_dormant).
+/// M(pt_active; x_active) is computed by applying the transposition of matrix M
+/// to the vector of values of a polynomial eq_poly(pt_active).
+/// Lincheck expects a matrix of size N*2^a x M*2^a, and it will be treated as matrix from N chunks of
+/// size 2^a to M chunks of size 2^a. Inside of a chunk, k-th bit of an index corresponds to the variable
+/// active_vars[k].
+pub struct Lincheck<'a, const N: usize, const M: usize, L: LinOp> {
+    matrix: L,
+    polys: Cow<'a, [Vec<F128>; N]>,
+    pt: Vec<F128>,
+    num_vars: usize,
+    active_vars: Vec<usize>,
+    initial_claims: [F128; M],
+    bitsliced_restriction: bool,
 }
 
-impl<A: LinOp, B: LinOp> Composition<A, B> {
-    pub fn new(a: A, b: B) -> Self {
-        assert!(b.n_out() == a.n_in());
-        Self { a, b }
+impl<'a, const N: usize, const M: usize, L: LinOp + 'a> Lincheck<'a, N, M, L> {
+    pub fn new(polys: [Vec<F128>; N], pt: Vec<F128>, matrix: L, num_active_vars: usize, initial_claims: [F128; M]) -> Self {
+        Self::with_active_vars(Cow::Owned(polys), pt, matrix, (0..num_active_vars).collect(), initial_claims)
     }
-}
 
-impl<A: LinOp, B: LinOp> LinOp for Composition<A, B> {
-    fn n_in(&self) -> usize {
-        self.b.n_in()
+    /// Same as new, but borrows polynomials instead of taking ownership. Lincheck only reads them once
+    /// during restriction, so there is no need to clone the witness.
+    pub fn new_borrowed(polys: &'a [Vec<F128>; N], pt: Vec<F128>, matrix: L, num_active_vars: usize, initial_claims: [F128; M]) -> Self {
+        Self::with_active_vars(Cow::Borrowed(polys), pt, matrix, (0..num_active_vars).collect(), initial_claims)
     }
-    
-    fn n_out(&self) -> usize {
-        self.a.n_out()
+
+    /// Most general constructor, with active variables given explicitly.
+    pub fn with_active_vars(polys: Cow<'a, [Vec<F128>; N]>, pt: Vec<F128>, matrix: L, active_vars: Vec<usize>, initial_claims: [F128; M]) -> Self {
+        let num_active_vars = active_vars.len();
+        assert!(matrix.n_in() == N * (1 << num_active_vars));
+        assert!(matrix.n_out() == M * (1 << num_active_vars));
+        let num_vars = pt.len();
+        let mut is_active = vec![false; num_vars];
+        for &v in active_vars.iter() {
+            assert!(v < num_vars, "Active variable out of range.");
+            assert!(!is_active[v], "Active variables must be distinct.");
+            is_active[v] = true;
+        }
+        for i in 0..N {
+            assert!(polys[i].len() == 1 << num_vars);
+        }
+        Self { matrix, polys, pt, num_vars, active_vars, initial_claims, bitsliced_restriction: false }
     }
 
-    fn apply(&self, input: &[F128], output: &mut [F128]) {
-        let mid = self.b.n_out();
-        let mut tmp = vec![F128::zero(); mid];
-        self.b.apply(input, &mut tmp);
-        self.a.apply(&tmp, output);
+    /// Switches restriction of dormant variables to the bit-sliced algorithm. Only available if active variables
+    /// form a prefix.
+    pub fn with_bitsliced_restriction(mut self) -> Self {
+        assert!(self.active_vars.iter().enumerate().all(|(i, &v)| i == v));
+        self.bitsliced_restriction = true;
+        self
     }
 
-    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
-        let mid = self.b.n_out();
-        let mut tmp = vec![F128::zero(); mid];
-        self.a.apply_transposed(input, &mut tmp);
-        self.b.apply_transposed(&tmp, output);
+    pub fn folding_challenge(self, gamma: F128) -> PreparedLincheck {
+        let Self { matrix, polys, pt, num_vars: _, active_vars, initial_claims, bitsliced_restriction } = self;
+        let mut batch = LincheckBatch::with_polys(polys, active_vars.len());
+        if bitsliced_restriction {
+            batch = batch.with_bitsliced_restriction();
+        }
+        batch.add_instance(matrix, pt, active_vars, initial_claims.to_vec());
+        batch.folding_challenge(gamma)
     }
 }
 
-pub struct MatrixSum<A: LinOp, B: LinOp> {
-    a: A,
-    b: B,
+struct LincheckInstance<'a> {
+    matrix: Box<dyn LinOp + 'a>,
+    group: usize,
+    pt_active: Vec<F128>,
+    initial_claims: Vec<F128>,
 }
 
-impl<A: LinOp, B: LinOp> MatrixSum<A, B> {
-    pub fn new(a: A, b: B) -> Self {
-        assert!(b.n_in() == a.n_in());
-        assert!(b.n_out() == a.n_out());
-        Self { a, b }
-    }
+/// A group of instances which share the same active variables and the same dormant coordinates, and thus
+/// the same restriction of input polynomials.
+#[derive(Clone, Debug, PartialEq)]
+struct LincheckGroup {
+    pt: Vec<F128>,
+    active_vars: Vec<usize>,
+}
+
+/// Batches several lincheck instances acting on the same input polynomials, but with (possibly) different
+/// matrices, points and active variable sets, into a single Prodcheck.
+/// All instances must have the same number of active variables, so that they share sumcheck challenges.
+/// Claims of all instances are concatenated in order of addition, and folded using consecutive powers of gamma.
+/// Instances with the same active variables and the same dormant coordinates are merged, so that the input
+/// polynomials are restricted only once per such group. Each group produces N output claims, in a point given
+/// by PreparedLincheck::output_points.
+pub struct LincheckBatch<'a, const N: usize> {
+    polys: Cow<'a, [Vec<F128>; N]>,
+    num_vars: usize,
+    num_active_vars: usize,
+    instances: Vec<LincheckInstance<'a>>,
+    groups: Vec<LincheckGroup>,
+    bitsliced_restriction: bool,
 }
 
-impl<A: LinOp, B: LinOp> LinOp for MatrixSum<A, B> {
-    fn n_in(&self) -> usize {
-        self.a.n_in()
+impl<'a, const N: usize> LincheckBatch<'a, N> {
+    pub fn new(polys: [Vec<F128>; N], num_active_vars: usize) -> Self {
+        Self::with_polys(Cow::Owned(polys), num_active_vars)
     }
 
-    fn n_out(&self) -> usize {
-        self.a.n_out()
+    pub fn new_borrowed(polys: &'a [Vec<F128>; N], num_active_vars: usize) -> Self {
+        Self::with_polys(Cow::Borrowed(polys), num_active_vars)
     }
 
-    fn apply(&self, input: &[F128], output: &mut [F128]) {
-        self.a.apply(input, output);
-        self.b.apply(input, output);
+    pub fn with_polys(polys: Cow<'a, [Vec<F128>; N]>, num_active_vars: usize) -> Self {
+        let num_vars = log2_exact(polys[0].len());
+        for i in 0..N {
+            assert!(polys[i].len() == 1 << num_vars);
+        }
+        assert!(num_vars >= num_active_vars);
+        Self { polys, num_vars, num_active_vars, instances: vec![], groups: vec![], bitsliced_restriction: false }
     }
 
-    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
-        self.a.apply_transposed(input, output);
-        self.b.apply_transposed(input, output);
+    /// See Lincheck::with_bitsliced_restriction.
+    pub fn with_bitsliced_restriction(mut self) -> Self {
+        self.bitsliced_restriction = true;
+        self
     }
-}
 
-pub struct IdentityMatrix {
-    size: usize,
-}
+    /// Adds an instance claiming that (matrix * polys)(pt) = initial_claims, where matrix acts on
+    /// active_vars. Returns the index of the group this instance belongs to.
+    pub fn add_instance(&mut self, matrix: impl LinOp + 'a, pt: Vec<F128>, active_vars: Vec<usize>, initial_claims: Vec<F128>) -> usize {
+        assert!(pt.len() == self.num_vars);
+        assert!(active_vars.len() == self.num_active_vars);
+        assert!(matrix.n_in() == N * (1 << self.num_active_vars));
+        assert!(matrix.n_out() == initial_claims.len() * (1 << self.num_active_vars));
+        assert!(self.bitsliced_restriction == false || active_vars.iter().enumerate().all(|(i, &v)| i == v));
+
+        let mut is_active = vec![false; self.num_vars];
+        for &v in active_vars.iter() {
+            assert!(v < self.num_vars, "Active variable out of range.");
+            assert!(!is_active[v], "Active variables must be distinct.");
+            is_active[v] = true;
+        }
 
-impl IdentityMatrix {
-    pub fn new(size: usize) -> Self {
-        Self { size }
+        // Coordinates of active variables do not matter for the restriction, so instances with different
+        // active coordinates can still share a group.
+        let (pt_active, pt_dormant) = split_point(&pt, &active_vars);
+        let group = self.groups.iter().position(|g| {
+            g.active_vars == active_vars && split_point(&g.pt, &g.active_vars).1 == pt_dormant
+        }).unwrap_or_else(|| {
+            self.groups.push(LincheckGroup { pt: pt.clone(), active_vars: active_vars.clone() });
+            self.groups.len() - 1
+        });
+
+        self.instances.push(LincheckInstance { matrix: Box::new(matrix), group, pt_active, initial_claims });
+        group
     }
-}
 
-impl LinOp for IdentityMatrix {
-    fn n_in(&self) -> usize {
-        self.size
-    }
+    pub fn folding_challenge(self, gamma: F128) -> PreparedLincheck {
+        let Self { polys, num_vars: _, num_active_vars, instances, groups, bitsliced_restriction } = self;
+        let chunk_size = 1 << num_active_vars;
+
+        let mut q_groups = vec![vec![F128::zero(); N * chunk_size]; groups.len()];
+        let mut claims = vec![];
+        let mut gamma_pow = F128::one();
+
+        for LincheckInstance { matrix, group, pt_active, initial_claims } in instances {
+            let eq = eq_poly(&pt_active);
+            let mut gamma_eqs = Vec::with_capacity(initial_claims.len() * chunk_size);
+            for _ in 0..initial_claims.len() {
+                gamma_eqs.extend(eq.iter().map(|x| *x * gamma_pow));
+                gamma_pow *= gamma;
+            }
+            matrix.apply_transposed(&gamma_eqs, &mut q_groups[group]);
+            // q(x) += gamma^{offset} * M(pt_active, x)
+            claims.extend(initial_claims);
+        }
 
-    fn n_out(&self) -> usize {
-        self.size
-    }
+        let polys : Vec<&[F128]> = polys.iter().map(|p| p.as_slice()).collect();
 
-    fn apply(&self, input: &[F128], output: &mut [F128]) {
-        for i in 0..self.size {
-            output[i] += input[i]
+        let mut p_polys = vec![];
+        let mut q_polys = vec![];
+        for (group, q) in groups.iter().zip(q_groups.into_iter()) {
+            let (_, pt_dormant) = split_point(&group.pt, &group.active_vars);
+            p_polys.extend(restrict_dormant(&polys, &group.active_vars, &pt_dormant, bitsliced_restriction));
+            q_polys.extend(q.chunks(chunk_size).map(|c| c.to_vec()));
         }
-    }
 
-    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
-        for i in 0..self.size {
-            output[i] += input[i]
+        let claim = evaluate_univar(&claims, gamma);
+
+        PreparedLincheck{
+            object: Prodcheck::new(p_polys, q_polys, claim, false, false).with_fused_bind(),
+            groups,
         }
     }
 }
 
-/// Represents a linear sumcheck of the form
-/// M(pt_{n-a}, ... pt_{n-1}; x_{n-a}, ..., x_{n-1}) * P(pt_0, ..., pt_{n-a-1}, x_{n-a}, ..., x_{n-1}),
-/// where `a` is a number of "active" variables.
-/// It is very small, and the main computational work is computing the restriction
-/// P(x_0, ..., x_{a-1}, pt_a, ..., pt_{n-1}).
-/// M(pt_0, ... pt_{a-1}; x_0, ..., x_{a-1}) is computed by applying the transposition of matrix M
-/// to the vector of values of a polynomial eq_poly(pt[0..a]).
-/// Lincheck expects a matrix of size N*2^a x M*2^a, and it will be treated as matrix from N chunks of
-/// size 2^a to M chunks of size 2^a.
-pub struct Lincheck<const N: usize, const M: usize, L: LinOp> {
-    matrix: L,
-    polys: [Vec<F128>; N],
-    pt: Vec<F128>,
-    num_vars: usize,
-    num_active_vars: usize,
-    initial_claims: [F128; M],
+/// Verifier side: computes the expected final claim of Lincheck from the evaluations p_evs returned by the prover,
+/// the initial point pt, active variables, challenges rs and the folding challenge gamma.
+/// Requires applying the transposed matrix once, which is cheap for small number of active variables.
+pub fn lincheck_final_claim<L: LinOp + ?Sized>(
+    matrix: &L,
+    pt: &[F128],
+    active_vars: &[usize],
+    rs: &[F128],
+    gamma: F128,
+    p_evs: &[F128],
+) -> F128 {
+    let chunk_size = 1 << active_vars.len();
+    assert!(rs.len() == active_vars.len());
+    assert!(matrix.n_in() == p_evs.len() * chunk_size);
+    let m = matrix.n_out() / chunk_size;
+
+    let (pt_active, _) = split_point(pt, active_vars);
+    let eq1 = eq_poly(&pt_active);
+    let eq0 = eq_poly(rs);
+
+    let mut adj_eq_vec = Vec::with_capacity(m * chunk_size);
+    let mut mult = F128::one();
+    for _ in 0..m {
+        adj_eq_vec.extend(eq1.iter().map(|x| *x * mult));
+        mult *= gamma;
+    }
+
+    let mut target = vec![F128::zero(); matrix.n_in()];
+    matrix.apply_transposed(&adj_eq_vec, &mut target);
+
+    target.chunks(chunk_size).zip(p_evs.iter()).map(|(chunk, p_ev)| {
+        chunk.iter().zip(eq0.iter()).fold(F128::zero(), |acc, (a, b)| acc + *a * b) * p_ev
+    }).fold(F128::zero(), |a, b| a + b)
 }
 
-impl<const N: usize, const M: usize, L: LinOp> Lincheck<N, M, L> {
-    pub fn new(polys: [Vec<F128>; N], pt: Vec<F128>, matrix: L, num_active_vars: usize, initial_claims: [F128; M]) -> Self {
-        assert!(matrix.n_in() == N * (1 << num_active_vars));
-        assert!(matrix.n_out() == M * (1 << num_active_vars));
-        let num_vars = pt.len();
-        assert!(num_vars >= num_active_vars);
-        for i in 0..N {
-            assert!(polys[i].len() == 1 << num_vars);
-        }
-        Self { matrix, polys, pt, num_vars, num_active_vars, initial_claims }
-    } 
+/// Splits point into active and dormant coordinates. Active coordinates are returned in the order of
+/// active_vars, and dormant ones in increasing order.
+pub fn split_point(pt: &[F128], active_vars: &[usize]) -> (Vec<F128>, Vec<F128>) {
+    let mut is_active = vec![false; pt.len()];
+    active_vars.iter().map(|&v| is_active[v] = true).count();
+    let pt_active = active_vars.iter().map(|&v| pt[v]).collect();
+    let pt_dormant = (0..pt.len()).filter(|&v| !is_active[v]).map(|v| pt[v]).collect();
+    (pt_active, pt_dormant)
+}
 
-    pub fn folding_challenge(self, gamma: F128) -> PreparedLincheck {
-        let chunk_size = 1 << self.num_active_vars;
-        let pt_active = &self.pt[ .. self.num_active_vars];
-        let pt_dormant = &self.pt[self.num_active_vars .. ];
-        // Restrict.
-        
-        let eq_dormant = eq_poly(&pt_dormant);
-        let mut p_polys = vec![vec![F128::zero(); 1 << self.num_active_vars]; N];
-        
+/// Assembles the point in which lincheck's output claims hold: coordinates of active variables are
+/// replaced by sumcheck challenges, and dormant ones are taken from the initial point.
+pub fn assemble_point(pt: &[F128], active_vars: &[usize], challenges: &[F128]) -> Vec<F128> {
+    assert!(active_vars.len() == challenges.len());
+    let mut ret = pt.to_vec();
+    active_vars.iter().zip(challenges.iter()).map(|(&v, r)| ret[v] = *r).count();
+    ret
+}
 
-        self.polys.into_iter().enumerate().map(|(i, poly)| {
-            let poly_chunks = poly.chunks(chunk_size);
-            poly_chunks.enumerate().map(|(j, chunk)| {
-                p_polys[i].iter_mut().zip(chunk.iter()).map(|(p, c)| *p += eq_dormant[j] * c).count();
-        }).count()}).count();
-
-        let mut gamma_pows = Vec::with_capacity(M);
-        let mut tmp = F128::one();
-        for _ in 0..M {
-            gamma_pows.push(tmp);
-            tmp *= gamma;
-        }
-        let eq = eq_poly(&pt_active);
-        let gamma_eqs: Vec<_> = gamma_pows.iter()
-            .map(|gpow| (0..(1 << self.num_active_vars))
-            .map(|i| *gpow * eq[i]))
-            .flatten()
-            .collect();
+/// Computes P(x_active, pt_dormant) for every polynomial.
+/// The polynomial is split into cache-sized blocks, each of which is processed in parallel into its own
+/// accumulator, and the accumulators are then summed.
+/// If active variables form a prefix, one can alternatively request bit-sliced restriction (see restrict_high),
+/// which avoids multiplications altogether. It is profitable when multiplication is slow compared to table
+/// lookups, which depends on the backend, so it is not the default.
+pub fn restrict_dormant(polys: &[&[F128]], active_vars: &[usize], pt_dormant: &[F128], bitsliced: bool) -> Vec<Vec<F128>> {
+    let num_active_vars = active_vars.len();
+    let num_vars = num_active_vars + pt_dormant.len();
+    for poly in polys {
+        assert!(poly.len() == 1 << num_vars);
+    }
 
-        let mut q = vec![F128::zero(); N * (1 << self.num_active_vars)];
-        self.matrix.apply_transposed(&gamma_eqs, &mut q);
-        // q(x) = M(pt[0..a], x)
+    let is_prefix = active_vars.iter().enumerate().all(|(i, &v)| i == v);
+    if bitsliced {
+        assert!(is_prefix, "Bit-sliced restriction requires active variables to be a prefix.");
+        return polys.iter().map(|poly| restrict_high(poly, pt_dormant)).collect();
+    }
 
-        let mut q_polys = vec![];
-        for _ in 0..N {
-            let tmp = q.split_off(1 << self.num_active_vars);
-            q_polys.push(q);
-            q = tmp;
-        }
-        //sanity:
-        assert_eq!(q.len(), 0);
+    let eq_dormant = eq_poly(pt_dormant);
+    let chunk_size = 1 << num_active_vars;
+    let block_size = std::cmp::max(RESTRICT_BLOCK_SIZE, chunk_size);
+    let zero = || vec![F128::zero(); chunk_size];
+    let sum = |mut a: Vec<F128>, b: Vec<F128>| {
+        a.iter_mut().zip(b.iter()).map(|(a, b)| *a += b).count();
+        a
+    };
+
+    if is_prefix {
+        return polys.iter().map(|poly| {
+            #[cfg(not(feature = "parallel"))]
+            let iter = poly.chunks(block_size);
+            #[cfg(feature = "parallel")]
+            let iter = poly.par_chunks(block_size);
+
+            let iter = iter.enumerate().map(|(block_idx, block)| {
+                let mut p = zero();
+                let offset = block_idx * (block_size / chunk_size);
+                block.chunks(chunk_size).enumerate().map(|(j, chunk)| {
+                    let e = eq_dormant[offset + j];
+                    p.iter_mut().zip(chunk.iter()).map(|(p, c)| *p += e * c).count();
+                }).count();
+                p
+            });
+
+            #[cfg(not(feature = "parallel"))]
+            let ret = iter.fold(zero(), sum);
+            #[cfg(feature = "parallel")]
+            let ret = iter.reduce(zero, sum);
+
+            ret
+        }).collect();
+    }
 
-        let claim = evaluate_univar(&self.initial_claims, gamma);
+    // For every byte of an index, precompute its contribution to active and dormant indices.
+    let mut is_active = vec![false; num_vars];
+    let mut active_pos = vec![0; num_vars];
+    let mut dormant_pos = vec![0; num_vars];
+    active_vars.iter().enumerate().map(|(k, &v)| {is_active[v] = true; active_pos[v] = k}).count();
+    let mut d = 0;
+    for v in 0..num_vars {
+        if !is_active[v] {
+            dormant_pos[v] = d;
+            d += 1;
+        }
+    }
 
-        PreparedLincheck{
-            object: Prodcheck::new(p_polys, q_polys, claim, false, false)
+    let num_bytes = (num_vars + 7) / 8;
+    let mut active_table = vec![0usize; 256 * num_bytes];
+    let mut dormant_table = vec![0usize; 256 * num_bytes];
+    for b in 0..num_bytes {
+        for byte in 0..256 {
+            for bit in 0..8 {
+                let v = 8 * b + bit;
+                if v >= num_vars || (byte >> bit) % 2 == 0 {
+                    continue;
+                }
+                if is_active[v] {
+                    active_table[256 * b + byte] |= 1 << active_pos[v];
+                } else {
+                    dormant_table[256 * b + byte] |= 1 << dormant_pos[v];
+                }
+            }
         }
     }
+
+    polys.iter().map(|poly| {
+        #[cfg(not(feature = "parallel"))]
+        let iter = poly.chunks(block_size);
+        #[cfg(feature = "parallel")]
+        let iter = poly.par_chunks(block_size);
+
+        let iter = iter.enumerate().map(|(block_idx, block)| {
+            let mut p = zero();
+            for (i, value) in block.iter().enumerate() {
+                let x = block_idx * block_size + i;
+                let mut a = 0;
+                let mut d = 0;
+                for b in 0..num_bytes {
+                    let byte = (x >> (8 * b)) & 255;
+                    a |= active_table[256 * b + byte];
+                    d |= dormant_table[256 * b + byte];
+                }
+                p[a] += eq_dormant[d] * value;
+            }
+            p
+        });
+
+        #[cfg(not(feature = "parallel"))]
+        let ret = iter.fold(zero(), sum);
+        #[cfg(feature = "parallel")]
+        let ret = iter.reduce(zero, sum);
+
+        ret
+    }).collect()
 }
 
+/// Number of polynomial entries processed by a single task in restrict_dormant.
+const RESTRICT_BLOCK_SIZE: usize = 1 << 14;
+
 pub struct PreparedLincheck {
-    object: Prodcheck
+    object: Prodcheck,
+    groups: Vec<LincheckGroup>,
 }
 
 impl PreparedLincheck {
+    /// Points in which output claims hold, one per group of instances. Must be called after all rounds.
+    /// Output claims p_evs[g * N .. (g + 1) * N] are evaluations of input polynomials in the g-th point.
+    pub fn output_points(&self) -> Vec<Vec<F128>> {
+        self.groups.iter().map(|g| assemble_point(&g.pt, &g.active_vars, &self.object.challenges)).collect()
+    }
+
     pub fn finish(self) -> LincheckOutput{
         self.object.finish()
     }
@@ -275,9 +462,6 @@ mod tests {
         fn apply(&self, input: &[F128], output: &mut [F128]) {
             assert!(input.len() == self.n_in);
             assert!(output.len() == self.n_out);
-            for i in 0..self.n_out {
-                output[i] = F128::zero();
-            }
             for i in 0..self.n_in {
                 for j in 0..self.n_out {
                     output[j] += self.entries[j][i] * input[i];
@@ -288,9 +472,6 @@ mod tests {
         fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
             assert!(input.len() == self.n_out);
             assert!(output.len() == self.n_in);
-            for i in 0..self.n_in {
-                output[i] = F128::zero();
-            }
             for i in 0..self.n_out {
                 for j in 0..self.n_in {
                     output[j] += self.entries[i][j] * input[i];
@@ -424,4 +605,206 @@ mod tests {
 
     }
 
+
+    #[test]
+    fn lincheck_arbitrary_active_vars() {
+        let rng = &mut OsRng;
+
+        let num_vars = 12;
+        let active_vars = vec![11, 1, 3, 8, 5];
+        let num_active_vars = active_vars.len();
+
+        let entries = (0..1 << num_active_vars).map(|_| {
+            (0..1 << num_active_vars).map(|_| F128::rand(rng)).collect()
+        }).collect();
+        let linop = GenericLinop::new(entries);
+
+        let pt : Vec<_> = (0..num_vars).map(|_| F128::rand(rng)).collect();
+        let poly : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+
+        // Apply the matrix along active axes: gather every fiber, apply, and scatter back.
+        let dormant_vars : Vec<_> = (0..num_vars).filter(|v| !active_vars.contains(v)).collect();
+        let to_index = |a: usize, d: usize| {
+            let mut x = 0;
+            active_vars.iter().enumerate().map(|(k, v)| x |= ((a >> k) & 1) << v).count();
+            dormant_vars.iter().enumerate().map(|(k, v)| x |= ((d >> k) & 1) << v).count();
+            x
+        };
+        let mut l_p = vec![F128::zero(); 1 << num_vars];
+        for d in 0..1 << dormant_vars.len() {
+            let fiber : Vec<_> = (0..1 << num_active_vars).map(|a| poly[to_index(a, d)]).collect();
+            let mut image = vec![F128::zero(); 1 << num_active_vars];
+            linop.apply(&fiber, &mut image);
+            (0..1 << num_active_vars).map(|a| l_p[to_index(a, d)] = image[a]).count();
+        }
+
+        let initial_claim = evaluate(&l_p, &pt);
+
+        let prover = Lincheck::<1, 1, _>::with_active_vars(Cow::Owned([poly.clone()]), pt.clone(), linop.clone(), active_vars.clone(), [initial_claim]);
+        let mut prover = prover.folding_challenge(F128::rand(rng));
+
+        let mut rs = vec![];
+        let mut claim = initial_claim;
+
+        for _ in 0..num_active_vars {
+            let rpoly = prover.round_msg().coeffs(claim);
+            let r = F128::rand(rng);
+            claim = evaluate_univar(&rpoly, r);
+            prover.bind(r);
+            rs.push(r);
+        };
+
+        let LincheckOutput {p_evs, q_evs} = prover.finish();
+
+        let (pt_active, _) = split_point(&pt, &active_vars);
+        let mut target = vec![F128::zero(); 1 << num_active_vars];
+        linop.apply_transposed(&eq_poly(&pt_active), &mut target);
+        assert!(q_evs[0] == evaluate(&target, &rs));
+        assert!(p_evs[0] * q_evs[0] == claim);
+
+        assert!(p_evs[0] == evaluate(&poly, &assemble_point(&pt, &active_vars, &rs)));
+    }
+
+
+    #[test]
+    fn restrict_dormant_paths_agree() {
+        let rng = &mut OsRng;
+        let num_vars = 12;
+        let num_active_vars = 5;
+        let pt : Vec<_> = (0..num_vars).map(|_| F128::rand(rng)).collect();
+        let poly : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let polys = [poly.as_slice()];
+
+        let active_vars : Vec<_> = (0..num_active_vars).collect();
+        let (_, pt_dormant) = split_point(&pt, &active_vars);
+
+        let direct = restrict_dormant(&polys, &active_vars, &pt_dormant, false);
+        let bitsliced = restrict_dormant(&polys, &active_vars, &pt_dormant, true);
+        assert!(direct == bitsliced);
+
+        // Same active set, but listed in a different order, goes through generic path.
+        let mut permuted_vars = active_vars.clone();
+        permuted_vars.swap(0, 1);
+        let permuted = restrict_dormant(&polys, &permuted_vars, &pt_dormant, false);
+        for a in 0..1 << num_active_vars {
+            let swapped = (a & !3) | ((a & 1) << 1) | ((a >> 1) & 1);
+            assert!(permuted[0][swapped] == direct[0][a]);
+        }
+
+        let rs : Vec<_> = (0..num_active_vars).map(|_| F128::rand(rng)).collect();
+        assert!(evaluate(&direct[0], &rs) == evaluate(&poly, &assemble_point(&pt, &active_vars, &rs)));
+    }
+
+    #[test]
+    fn bitsliced_restriction_few_dormant_vars() {
+        let rng = &mut OsRng;
+        let num_vars = 8;
+        let poly : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let polys = [poly.as_slice()];
+
+        for num_dormant_vars in 1..4 {
+            let pt_dormant : Vec<_> = (0..num_dormant_vars).map(|_| F128::rand(rng)).collect();
+            let active_vars : Vec<_> = (0 .. num_vars - num_dormant_vars).collect();
+            let direct = restrict_dormant(&polys, &active_vars, &pt_dormant, false);
+            let bitsliced = restrict_dormant(&polys, &active_vars, &pt_dormant, true);
+            assert!(direct == bitsliced);
+        }
+    }
+
+
+    #[test]
+    fn lincheck_batch_works() {
+        let rng = &mut OsRng;
+
+        let num_vars = 12;
+        let num_active_vars = 4;
+        let chunk = 1 << num_active_vars;
+
+        let rand_linop = |n_in: usize, n_out: usize, rng: &mut OsRng| GenericLinop::new(
+            (0..n_out).map(|_| (0..n_in).map(|_| F128::rand(rng)).collect()).collect()
+        );
+
+        let polys : [Vec<F128>; 2] = [0, 1].map(|_| (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect());
+
+        // Evaluates (matrix * polys)(pt), where matrix acts on a prefix of variables.
+        let claims_for = |m: &GenericLinop, pt: &[F128]| -> Vec<F128> {
+            let n_out = m.n_out / chunk;
+            let mut images = vec![vec![F128::zero(); 1 << num_vars]; n_out];
+            for d in 0 .. 1 << (num_vars - num_active_vars) {
+                let input : Vec<_> = polys.iter().map(|p| p[d * chunk .. (d + 1) * chunk].to_vec()).flatten().collect();
+                let mut output = vec![F128::zero(); m.n_out];
+                m.apply(&input, &mut output);
+                for k in 0..n_out {
+                    images[k][d * chunk .. (d + 1) * chunk].copy_from_slice(&output[k * chunk .. (k + 1) * chunk]);
+                }
+            }
+            images.iter().map(|img| evaluate(img, pt)).collect()
+        };
+
+        let pt0 : Vec<_> = (0..num_vars).map(|_| F128::rand(rng)).collect();
+        // Same dormant coordinates, different active ones.
+        let mut pt1 = pt0.clone();
+        (0..num_active_vars).map(|i| pt1[i] = F128::rand(rng)).count();
+        let pt2 : Vec<_> = (0..num_vars).map(|_| F128::rand(rng)).collect();
+
+        let m0 = rand_linop(2 * chunk, 3 * chunk, rng);
+        let m1 = rand_linop(2 * chunk, chunk, rng);
+        let m2 = rand_linop(2 * chunk, 2 * chunk, rng);
+
+        let instances = [(&m0, &pt0), (&m1, &pt1), (&m2, &pt2)];
+        let claims : Vec<_> = instances.iter().map(|(m, pt)| claims_for(m, pt)).collect();
+
+        let mut batch = LincheckBatch::new_borrowed(&polys, num_active_vars);
+        let active_vars : Vec<_> = (0..num_active_vars).collect();
+        let groups : Vec<_> = instances.iter().zip(claims.iter()).map(|((m, pt), c)| {
+            batch.add_instance((*m).clone(), pt.to_vec(), active_vars.clone(), c.clone())
+        }).collect();
+        assert!(groups == vec![0, 0, 1]);
+
+        let gamma = F128::rand(rng);
+        let mut prover = batch.folding_challenge(gamma);
+
+        let all_claims : Vec<_> = claims.iter().flatten().map(|x| *x).collect();
+        let mut claim = evaluate_univar(&all_claims, gamma);
+        let mut rs = vec![];
+        for _ in 0..num_active_vars {
+            let rpoly = prover.round_msg().coeffs(claim);
+            let r = F128::rand(rng);
+            claim = evaluate_univar(&rpoly, r);
+            prover.bind(r);
+            rs.push(r);
+        }
+
+        let points = prover.output_points();
+        let LincheckOutput { p_evs, q_evs } = prover.finish();
+        assert!(p_evs.len() == 4);
+
+        // Verifier recomputes q evaluations.
+        let mut expected_q = vec![F128::zero(); 4];
+        let mut gamma_pow = F128::one();
+        for (((m, pt), c), g) in instances.iter().zip(claims.iter()).zip(groups.iter()) {
+            let eq = eq_poly(&pt[..num_active_vars]);
+            let mut gamma_eqs = vec![];
+            for _ in 0..c.len() {
+                gamma_eqs.extend(eq.iter().map(|x| *x * gamma_pow));
+                gamma_pow *= gamma;
+            }
+            let mut target = vec![F128::zero(); 2 * chunk];
+            m.apply_transposed(&gamma_eqs, &mut target);
+            for j in 0..2 {
+                expected_q[2 * g + j] += evaluate(&target[j * chunk .. (j + 1) * chunk], &rs);
+            }
+        }
+        assert!(expected_q == q_evs);
+
+        let expected_claim = p_evs.iter().zip(q_evs.iter()).map(|(a, b)| *a * b).fold(F128::zero(), |a, b| a + b);
+        assert!(expected_claim == claim);
+
+        for g in 0..2 {
+            for j in 0..2 {
+                assert!(p_evs[2 * g + j] == evaluate(&polys[j], &points[g]));
+            }
+        }
+    }
+
 }
\ No newline at end of file
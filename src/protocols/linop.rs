@@ -0,0 +1,811 @@
+// This file contains the linear operator trait used by lincheck, and a collection of combinators which allow
+// to assemble complicated matrices (like the batched keccak layout) from simple pieces, without writing
+// index loops by hand.
+
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use crate::field::F128;
+
+pub trait LinOp {
+    fn n_in(&self) -> usize;
+    fn n_out(&self) -> usize;
+    
+    /// expects input of size n_in and output of size n_out
+    /// adds result to already existing output using +=
+    fn apply(&self, input: &[F128], output: &mut [F128]);
+    /// expects input of size n_out and output of size n_in
+    /// adds result to already existing output using +=
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]);
+
+    /// Materializes the matrix, returning n_out rows of size n_in. Applies the operator to every basis vector,
+    /// so it is only suitable for small matrices and debugging.
+    fn to_dense(&self) -> Vec<Vec<F128>> {
+        let mut rows = vec![vec![F128::zero(); self.n_in()]; self.n_out()];
+        let mut unit = vec![F128::zero(); self.n_in()];
+        let mut col = vec![F128::zero(); self.n_out()];
+        for i in 0..self.n_in() {
+            unit[i] = F128::one();
+            col.iter_mut().map(|x| *x = F128::zero()).count();
+            self.apply(&unit, &mut col);
+            unit[i] = F128::zero();
+            for j in 0..self.n_out() {
+                rows[j][i] = col[j];
+            }
+        }
+        rows
+    }
+}
+
+/// Checks that apply_transposed is indeed the transpose of apply, by testing <Mv, w> = <v, M^T w> for random
+/// v, w. Also checks that both of them accumulate into output instead of overwriting it.
+/// A single trial is wrong with probability at most 2/|F|, so it is enough for all practical purposes, but
+/// one can request more.
+pub fn check_adjoint<L: LinOp + ?Sized, RNG: Rng>(m: &L, rng: &mut RNG, trials: usize) -> bool {
+    let inner = |a: &[F128], b: &[F128]| a.iter().zip(b.iter()).fold(F128::zero(), |acc, (a, b)| acc + *a * b);
+
+    for _ in 0..trials {
+        let v : Vec<_> = (0..m.n_in()).map(|_| F128::rand(rng)).collect();
+        let w : Vec<_> = (0..m.n_out()).map(|_| F128::rand(rng)).collect();
+        let mv_init : Vec<_> = (0..m.n_out()).map(|_| F128::rand(rng)).collect();
+        let mtw_init : Vec<_> = (0..m.n_in()).map(|_| F128::rand(rng)).collect();
+
+        let mut mv = mv_init.clone();
+        let mut mtw = mtw_init.clone();
+        m.apply(&v, &mut mv);
+        m.apply_transposed(&w, &mut mtw);
+
+        // Remove initial values, if they were overwritten the check will fail.
+        mv.iter_mut().zip(mv_init.iter()).map(|(a, b)| *a += b).count();
+        mtw.iter_mut().zip(mtw_init.iter()).map(|(a, b)| *a += b).count();
+
+        if inner(&mv, &w) != inner(&v, &mtw) {
+            return false;
+        }
+    }
+    true
+}
+
+pub struct Composition<A: LinOp, B: LinOp> {
+    a: A,
+    b: B,
+}
+
+impl<A: LinOp, B: LinOp> Composition<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        assert!(b.n_out() == a.n_in());
+        Self { a, b }
+    }
+}
+
+impl<A: LinOp, B: LinOp> LinOp for Composition<A, B> {
+    fn n_in(&self) -> usize {
+        self.b.n_in()
+    }
+    
+    fn n_out(&self) -> usize {
+        self.a.n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        let mid = self.b.n_out();
+        let mut tmp = vec![F128::zero(); mid];
+        self.b.apply(input, &mut tmp);
+        self.a.apply(&tmp, output);
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        let mid = self.b.n_out();
+        let mut tmp = vec![F128::zero(); mid];
+        self.a.apply_transposed(input, &mut tmp);
+        self.b.apply_transposed(&tmp, output);
+    }
+}
+
+pub struct MatrixSum<A: LinOp, B: LinOp> {
+    a: A,
+    b: B,
+}
+
+impl<A: LinOp, B: LinOp> MatrixSum<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        assert!(b.n_in() == a.n_in());
+        assert!(b.n_out() == a.n_out());
+        Self { a, b }
+    }
+}
+
+impl<A: LinOp, B: LinOp> LinOp for MatrixSum<A, B> {
+    fn n_in(&self) -> usize {
+        self.a.n_in()
+    }
+
+    fn n_out(&self) -> usize {
+        self.a.n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        self.a.apply(input, output);
+        self.b.apply(input, output);
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        self.a.apply_transposed(input, output);
+        self.b.apply_transposed(input, output);
+    }
+}
+
+pub struct IdentityMatrix {
+    size: usize,
+}
+
+impl IdentityMatrix {
+    pub fn new(size: usize) -> Self {
+        Self { size }
+    }
+}
+
+impl LinOp for IdentityMatrix {
+    fn n_in(&self) -> usize {
+        self.size
+    }
+
+    fn n_out(&self) -> usize {
+        self.size
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        for i in 0..self.size {
+            output[i] += input[i]
+        }
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        for i in 0..self.size {
+            output[i] += input[i]
+        }
+    }
+}
+
+impl<L: LinOp + ?Sized> LinOp for Box<L> {
+    fn n_in(&self) -> usize {
+        (**self).n_in()
+    }
+
+    fn n_out(&self) -> usize {
+        (**self).n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        (**self).apply(input, output)
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        (**self).apply_transposed(input, output)
+    }
+}
+
+impl<L: LinOp + ?Sized> LinOp for &L {
+    fn n_in(&self) -> usize {
+        (**self).n_in()
+    }
+
+    fn n_out(&self) -> usize {
+        (**self).n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        (**self).apply(input, output)
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        (**self).apply_transposed(input, output)
+    }
+}
+
+/// Matrix c * A.
+pub struct Scaled<A: LinOp> {
+    a: A,
+    c: F128,
+}
+
+impl<A: LinOp> Scaled<A> {
+    pub fn new(a: A, c: F128) -> Self {
+        Self { a, c }
+    }
+}
+
+impl<A: LinOp> LinOp for Scaled<A> {
+    fn n_in(&self) -> usize {
+        self.a.n_in()
+    }
+
+    fn n_out(&self) -> usize {
+        self.a.n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        let mut tmp = vec![F128::zero(); self.a.n_out()];
+        self.a.apply(input, &mut tmp);
+        output.iter_mut().zip(tmp.iter()).map(|(o, t)| *o += self.c * t).count();
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        let mut tmp = vec![F128::zero(); self.a.n_in()];
+        self.a.apply_transposed(input, &mut tmp);
+        output.iter_mut().zip(tmp.iter()).map(|(o, t)| *o += self.c * t).count();
+    }
+}
+
+/// Matrix A^T. Simply swaps apply and apply_transposed.
+pub struct Transposed<A: LinOp> {
+    a: A,
+}
+
+impl<A: LinOp> Transposed<A> {
+    pub fn new(a: A) -> Self {
+        Self { a }
+    }
+}
+
+impl<A: LinOp> LinOp for Transposed<A> {
+    fn n_in(&self) -> usize {
+        self.a.n_out()
+    }
+
+    fn n_out(&self) -> usize {
+        self.a.n_in()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        self.a.apply_transposed(input, output)
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        self.a.apply(input, output)
+    }
+}
+
+/// Permutation matrix, output[i] = input[perm[i]].
+#[derive(Clone, Debug)]
+pub struct Permutation {
+    perm: Vec<usize>,
+}
+
+impl Permutation {
+    pub fn new(perm: Vec<usize>) -> Self {
+        let mut seen = vec![false; perm.len()];
+        for &p in perm.iter() {
+            assert!(p < perm.len(), "Index out of range.");
+            assert!(!seen[p], "Not a permutation.");
+            seen[p] = true;
+        }
+        Self { perm }
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut inv = vec![0; self.perm.len()];
+        for (i, &p) in self.perm.iter().enumerate() {
+            inv[p] = i;
+        }
+        Self { perm: inv }
+    }
+}
+
+impl LinOp for Permutation {
+    fn n_in(&self) -> usize {
+        self.perm.len()
+    }
+
+    fn n_out(&self) -> usize {
+        self.perm.len()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        for (i, &p) in self.perm.iter().enumerate() {
+            output[i] += input[p];
+        }
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        for (i, &p) in self.perm.iter().enumerate() {
+            output[p] += input[i];
+        }
+    }
+}
+
+/// Block-diagonal matrix diag(A_0, ..., A_{k-1}). Blocks are laid out contiguously both in input and output.
+/// For blocks of different types, use Box<dyn LinOp>.
+pub struct BlockDiagonal<A: LinOp> {
+    blocks: Vec<A>,
+    offsets_in: Vec<usize>,
+    offsets_out: Vec<usize>,
+}
+
+impl<A: LinOp> BlockDiagonal<A> {
+    pub fn new(blocks: Vec<A>) -> Self {
+        assert!(blocks.len() > 0);
+        let mut offsets_in = vec![0];
+        let mut offsets_out = vec![0];
+        for block in blocks.iter() {
+            offsets_in.push(offsets_in.last().unwrap() + block.n_in());
+            offsets_out.push(offsets_out.last().unwrap() + block.n_out());
+        }
+        Self { blocks, offsets_in, offsets_out }
+    }
+}
+
+impl<A: LinOp> LinOp for BlockDiagonal<A> {
+    fn n_in(&self) -> usize {
+        *self.offsets_in.last().unwrap()
+    }
+
+    fn n_out(&self) -> usize {
+        *self.offsets_out.last().unwrap()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        for (i, block) in self.blocks.iter().enumerate() {
+            block.apply(
+                &input[self.offsets_in[i] .. self.offsets_in[i + 1]],
+                &mut output[self.offsets_out[i] .. self.offsets_out[i + 1]]
+            );
+        }
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        for (i, block) in self.blocks.iter().enumerate() {
+            block.apply_transposed(
+                &input[self.offsets_out[i] .. self.offsets_out[i + 1]],
+                &mut output[self.offsets_in[i] .. self.offsets_in[i + 1]]
+            );
+        }
+    }
+}
+
+/// Kronecker product A x B. Input is treated as a_in x b_in table in row-major order, i.e. index is
+/// i * b_in + j, and output is a_out x b_out table in the same order.
+/// B acts on rows, and A acts on columns.
+pub struct Kronecker<A: LinOp, B: LinOp> {
+    a: A,
+    b: B,
+}
+
+impl<A: LinOp, B: LinOp> Kronecker<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+
+    fn apply_inner<FA: Fn(&[F128], &mut [F128]), FB: Fn(&[F128], &mut [F128])>(
+        input: &[F128],
+        output: &mut [F128],
+        (a_in, a_out): (usize, usize),
+        (b_in, b_out): (usize, usize),
+        fa: FA,
+        fb: FB,
+    ) {
+        let mut tmp = vec![F128::zero(); a_in * b_out];
+        for i in 0..a_in {
+            fb(&input[i * b_in .. (i + 1) * b_in], &mut tmp[i * b_out .. (i + 1) * b_out]);
+        }
+
+        let mut col = vec![F128::zero(); a_in];
+        let mut res = vec![F128::zero(); a_out];
+        for j in 0..b_out {
+            for i in 0..a_in {
+                col[i] = tmp[i * b_out + j];
+            }
+            res.iter_mut().map(|x| *x = F128::zero()).count();
+            fa(&col, &mut res);
+            for i in 0..a_out {
+                output[i * b_out + j] += res[i];
+            }
+        }
+    }
+}
+
+impl<A: LinOp, B: LinOp> LinOp for Kronecker<A, B> {
+    fn n_in(&self) -> usize {
+        self.a.n_in() * self.b.n_in()
+    }
+
+    fn n_out(&self) -> usize {
+        self.a.n_out() * self.b.n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        Self::apply_inner(
+            input,
+            output,
+            (self.a.n_in(), self.a.n_out()),
+            (self.b.n_in(), self.b.n_out()),
+            |i, o| self.a.apply(i, o),
+            |i, o| self.b.apply(i, o),
+        )
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        Self::apply_inner(
+            input,
+            output,
+            (self.a.n_out(), self.a.n_in()),
+            (self.b.n_out(), self.b.n_in()),
+            |i, o| self.a.apply_transposed(i, o),
+            |i, o| self.b.apply_transposed(i, o),
+        )
+    }
+}
+
+/// Embeds matrix A into a larger n_in x n_out matrix, filled with zeros elsewhere. A reads input starting
+/// from offset_in, and writes output starting from offset_out.
+pub struct Embed<A: LinOp> {
+    a: A,
+    n_in: usize,
+    n_out: usize,
+    offset_in: usize,
+    offset_out: usize,
+}
+
+impl<A: LinOp> Embed<A> {
+    pub fn new(a: A, n_in: usize, offset_in: usize, n_out: usize, offset_out: usize) -> Self {
+        assert!(offset_in + a.n_in() <= n_in);
+        assert!(offset_out + a.n_out() <= n_out);
+        Self { a, n_in, n_out, offset_in, offset_out }
+    }
+
+    /// Pads matrix with zeros on the right and on the bottom.
+    pub fn zero_pad(a: A, n_in: usize, n_out: usize) -> Self {
+        Self::new(a, n_in, 0, n_out, 0)
+    }
+}
+
+impl<A: LinOp> LinOp for Embed<A> {
+    fn n_in(&self) -> usize {
+        self.n_in
+    }
+
+    fn n_out(&self) -> usize {
+        self.n_out
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        self.a.apply(
+            &input[self.offset_in .. self.offset_in + self.a.n_in()],
+            &mut output[self.offset_out .. self.offset_out + self.a.n_out()]
+        )
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        self.a.apply_transposed(
+            &input[self.offset_out .. self.offset_out + self.a.n_out()],
+            &mut output[self.offset_in .. self.offset_in + self.a.n_in()]
+        )
+    }
+}
+
+/// Vertical stack of A and B, i.e. matrix which outputs concatenation of A(input) and B(input).
+pub struct Stack<A: LinOp, B: LinOp> {
+    a: A,
+    b: B,
+}
+
+impl<A: LinOp, B: LinOp> Stack<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        assert!(a.n_in() == b.n_in());
+        Self { a, b }
+    }
+}
+
+impl<A: LinOp, B: LinOp> LinOp for Stack<A, B> {
+    fn n_in(&self) -> usize {
+        self.a.n_in()
+    }
+
+    fn n_out(&self) -> usize {
+        self.a.n_out() + self.b.n_out()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        let (out_a, out_b) = output.split_at_mut(self.a.n_out());
+        self.a.apply(input, out_a);
+        self.b.apply(input, out_b);
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        let (in_a, in_b) = input.split_at(self.a.n_out());
+        self.a.apply_transposed(in_a, output);
+        self.b.apply_transposed(in_b, output);
+    }
+}
+
+/// Elementary operation of XorNetworkLinOp. Every gate assigns to its destination register(s), overwriting the
+/// previous value; destination must not overlap with the sources.
+#[derive(Clone, Debug)]
+pub enum XorGate {
+    /// reg[dst] = reg[srcs[0]] + ... + reg[srcs[k-1]]
+    Xor { dst: usize, srcs: Vec<usize> },
+    /// reg[dst] = reg[src]
+    Copy { dst: usize, src: usize },
+    /// Cyclic rotation of a block of registers, moving element i to position i + shift:
+    /// reg[dst + (i + shift) % len] = reg[src + i]
+    Rotate { dst: usize, src: usize, len: usize, shift: usize },
+}
+
+/// Matrix-free linear operator, described by a straight-line program of XOR / copy / rotate gates acting on
+/// a register file. Registers 0..n_in are initialized by the input, all other registers by zero, and the output
+/// is read from the registers listed in outputs.
+///
+/// Both apply and apply_transposed are derived from the same description: the transposed map is computed by
+/// running the program backwards, propagating adjoints from destinations to sources (and clearing adjoint of a
+/// destination, as its previous value is overwritten). This way they can never disagree.
+#[derive(Clone, Debug)]
+pub struct XorNetworkLinOp {
+    n_in: usize,
+    n_regs: usize,
+    gates: Vec<XorGate>,
+    outputs: Vec<usize>,
+}
+
+impl XorNetworkLinOp {
+    pub fn new(n_in: usize, n_regs: usize, gates: Vec<XorGate>, outputs: Vec<usize>) -> Self {
+        assert!(n_in <= n_regs);
+        for gate in gates.iter() {
+            match gate {
+                XorGate::Xor { dst, srcs } => {
+                    assert!(*dst < n_regs);
+                    for src in srcs {
+                        assert!(*src < n_regs);
+                        assert!(src != dst, "Destination must not overlap with sources.");
+                    }
+                },
+                XorGate::Copy { dst, src } => {
+                    assert!(*dst < n_regs && *src < n_regs);
+                    assert!(src != dst, "Destination must not overlap with sources.");
+                },
+                XorGate::Rotate { dst, src, len, shift: _ } => {
+                    assert!(dst + len <= n_regs && src + len <= n_regs);
+                    assert!(dst + len <= *src || src + len <= *dst, "Destination must not overlap with sources.");
+                },
+            }
+        }
+        for output in outputs.iter() {
+            assert!(*output < n_regs);
+        }
+        Self { n_in, n_regs, gates, outputs }
+    }
+}
+
+impl LinOp for XorNetworkLinOp {
+    fn n_in(&self) -> usize {
+        self.n_in
+    }
+
+    fn n_out(&self) -> usize {
+        self.outputs.len()
+    }
+
+    fn apply(&self, input: &[F128], output: &mut [F128]) {
+        let mut regs = vec![F128::zero(); self.n_regs];
+        regs[..self.n_in].copy_from_slice(&input[..self.n_in]);
+        for gate in self.gates.iter() {
+            match gate {
+                XorGate::Xor { dst, srcs } => {
+                    regs[*dst] = srcs.iter().fold(F128::zero(), |acc, src| acc + regs[*src]);
+                },
+                XorGate::Copy { dst, src } => {
+                    regs[*dst] = regs[*src];
+                },
+                XorGate::Rotate { dst, src, len, shift } => {
+                    for i in 0..*len {
+                        regs[dst + (i + shift) % len] = regs[src + i];
+                    }
+                },
+            }
+        }
+        for (o, r) in output.iter_mut().zip(self.outputs.iter()) {
+            *o += regs[*r];
+        }
+    }
+
+    fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+        let mut adj = vec![F128::zero(); self.n_regs];
+        for (i, r) in input.iter().zip(self.outputs.iter()) {
+            adj[*r] += i;
+        }
+        for gate in self.gates.iter().rev() {
+            match gate {
+                XorGate::Xor { dst, srcs } => {
+                    let a = adj[*dst];
+                    adj[*dst] = F128::zero();
+                    for src in srcs {
+                        adj[*src] += a;
+                    }
+                },
+                XorGate::Copy { dst, src } => {
+                    let a = adj[*dst];
+                    adj[*dst] = F128::zero();
+                    adj[*src] += a;
+                },
+                XorGate::Rotate { dst, src, len, shift } => {
+                    for i in 0..*len {
+                        let a = adj[dst + (i + shift) % len];
+                        adj[dst + (i + shift) % len] = F128::zero();
+                        adj[src + i] += a;
+                    }
+                },
+            }
+        }
+        for (o, a) in output.iter_mut().zip(adj[..self.n_in].iter()) {
+            *o += a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    // Dense matrix with random entries, used as a building block for tests.
+    struct Dense {
+        entries: Vec<Vec<F128>>,
+    }
+
+    impl Dense {
+        fn rand(n_in: usize, n_out: usize) -> Self {
+            let rng = &mut OsRng;
+            Self { entries: (0..n_out).map(|_| (0..n_in).map(|_| F128::rand(rng)).collect()).collect() }
+        }
+    }
+
+    impl LinOp for Dense {
+        fn n_in(&self) -> usize {
+            self.entries[0].len()
+        }
+
+        fn n_out(&self) -> usize {
+            self.entries.len()
+        }
+
+        fn apply(&self, input: &[F128], output: &mut [F128]) {
+            for j in 0..self.n_out() {
+                for i in 0..self.n_in() {
+                    output[j] += self.entries[j][i] * input[i];
+                }
+            }
+        }
+
+        fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+            for j in 0..self.n_out() {
+                for i in 0..self.n_in() {
+                    output[i] += self.entries[j][i] * input[j];
+                }
+            }
+        }
+    }
+
+    fn apply_vec(m: &impl LinOp, v: &[F128]) -> Vec<F128> {
+        let mut ret = vec![F128::zero(); m.n_out()];
+        m.apply(v, &mut ret);
+        ret
+    }
+
+    #[test]
+    fn combinators_are_adjoint() {
+        let rng = &mut OsRng;
+
+        assert!(check_adjoint(&Scaled::new(Dense::rand(3, 4), F128::rand(rng)), rng, 1));
+        assert!(check_adjoint(&Transposed::new(Dense::rand(3, 4)), rng, 1));
+        assert!(check_adjoint(&Permutation::new(vec![2, 0, 3, 1]), rng, 1));
+        assert!(check_adjoint(&BlockDiagonal::new(vec![Dense::rand(3, 4), Dense::rand(2, 5)]), rng, 1));
+        assert!(check_adjoint(&Kronecker::new(Dense::rand(3, 2), Dense::rand(4, 5)), rng, 1));
+        assert!(check_adjoint(&Embed::new(Dense::rand(3, 4), 7, 2, 6, 1), rng, 1));
+        assert!(check_adjoint(&Stack::new(Dense::rand(3, 4), Dense::rand(3, 2)), rng, 1));
+    }
+
+    #[test]
+    fn combinators_compute_expected_values() {
+        let rng = &mut OsRng;
+        let a = Dense::rand(3, 2);
+        let b = Dense::rand(4, 3);
+        let c = F128::rand(rng);
+
+        let v : Vec<_> = (0..6).map(|_| F128::rand(rng)).collect();
+        let av0 = apply_vec(&a, &v[0..3]);
+        let av1 = apply_vec(&a, &v[3..6]);
+
+        // Kronecker with identity on the left is the same as repeated block diagonal.
+        let lhs = apply_vec(&Kronecker::new(IdentityMatrix::new(2), Dense { entries: a.entries.clone() }), &v);
+        let rhs = apply_vec(&BlockDiagonal::new(vec![Dense { entries: a.entries.clone() }, Dense { entries: a.entries.clone() }]), &v);
+        assert!(lhs == rhs);
+        assert!(lhs == av0.iter().chain(av1.iter()).map(|x| *x).collect_vec());
+
+        let scaled = apply_vec(&Scaled::new(Dense { entries: a.entries.clone() }, c), &v[0..3]);
+        assert!(scaled == av0.iter().map(|x| *x * c).collect_vec());
+
+        let perm = Permutation::new(vec![2, 0, 1]);
+        let pv = apply_vec(&perm, &v[0..3]);
+        assert!(pv == vec![v[2], v[0], v[1]]);
+        assert!(apply_vec(&perm.inverse(), &pv) == v[0..3].to_vec());
+
+        let embedded = apply_vec(&Embed::new(Dense { entries: a.entries.clone() }, 5, 1, 4, 2), &v[0..5]);
+        let av = apply_vec(&a, &v[1..4]);
+        assert!(embedded == vec![F128::zero(), F128::zero(), av[0], av[1]]);
+
+        let stacked = apply_vec(&Stack::new(Dense { entries: a.entries.clone() }, Transposed::new(b)), &v[0..3]);
+        assert!(stacked[0..2] == av0);
+        assert!(stacked.len() == 6);
+    }
+
+    #[test]
+    fn xor_network_as_expected() {
+        let rng = &mut OsRng;
+        // out_0 = x_0 + x_1, out_1..out_4 = rot((x_0 + x_1, x_1, x_2, x_3), 1), out_5 = x_2
+        let m = XorNetworkLinOp::new(
+            4,
+            13,
+            vec![
+                XorGate::Xor { dst: 4, srcs: vec![0, 1] },
+                XorGate::Copy { dst: 5, src: 4 },
+                XorGate::Copy { dst: 6, src: 1 },
+                XorGate::Copy { dst: 7, src: 2 },
+                XorGate::Copy { dst: 8, src: 3 },
+                XorGate::Rotate { dst: 9, src: 5, len: 4, shift: 1 },
+                // Overwrite a register which is not used anymore.
+                XorGate::Copy { dst: 5, src: 2 },
+            ],
+            vec![4, 9, 10, 11, 12, 5],
+        );
+
+        let v : Vec<_> = (0..4).map(|_| F128::rand(rng)).collect();
+        assert!(apply_vec(&m, &v) == vec![v[0] + v[1], v[3], v[0] + v[1], v[1], v[2], v[2]]);
+        assert!(check_adjoint(&m, rng, 1));
+        assert!(check_adjoint(&Composition::new(m.clone(), Transposed::new(m)), rng, 1));
+    }
+
+
+    #[test]
+    fn to_dense_and_check_adjoint() {
+        let rng = &mut OsRng;
+        let a = Dense::rand(3, 4);
+        let entries = a.entries.clone();
+        assert!(a.to_dense() == entries);
+
+        let transposed = Transposed::new(a).to_dense();
+        for i in 0..4 {
+            for j in 0..3 {
+                assert!(transposed[j][i] == entries[i][j]);
+            }
+        }
+
+        assert!(check_adjoint(&Dense { entries: entries.clone() }, rng, 3));
+
+        // Wrong transpose must be caught.
+        struct BrokenTranspose(Dense);
+        impl LinOp for BrokenTranspose {
+            fn n_in(&self) -> usize { self.0.n_in() }
+            fn n_out(&self) -> usize { self.0.n_out() }
+            fn apply(&self, input: &[F128], output: &mut [F128]) { self.0.apply(input, output) }
+            fn apply_transposed(&self, input: &[F128], output: &mut [F128]) {
+                self.0.apply_transposed(input, output);
+                output[0] += input[1];
+            }
+        }
+        assert!(!check_adjoint(&BrokenTranspose(Dense { entries: entries.clone() }), rng, 1));
+
+        struct Overwrites(Dense);
+        impl LinOp for Overwrites {
+            fn n_in(&self) -> usize { self.0.n_in() }
+            fn n_out(&self) -> usize { self.0.n_out() }
+            fn apply(&self, input: &[F128], output: &mut [F128]) {
+                output.iter_mut().map(|x| *x = F128::zero()).count();
+                self.0.apply(input, output)
+            }
+            fn apply_transposed(&self, input: &[F128], output: &mut [F128]) { self.0.apply_transposed(input, output) }
+        }
+        assert!(!check_adjoint(&Overwrites(Dense { entries }), rng, 1));
+    }
+
+}
@@ -0,0 +1,289 @@
+// LogUp-style lookup argument, proving that every value of a witness polynomial w appears in a public table t.
+
+// The usual LogUp identity sum_i 1 / (X + w_i) = sum_j m_j / (X + t_j) only determines multiplicities modulo the
+// characteristic, so in characteristic 2 any value repeated an even number of times would cancel out. Instead, every
+// witness entry is weighted by eq(rho, i) for a random point rho, and multiplicities are field elements
+// m_j = sum_{i : w_i = t_j} eq(rho, i).
+// A value outside of the table then survives with the coefficient sum_{i in S} eq(rho, i), which is a non-zero
+// multilinear polynomial in rho.
+
+// For random alpha, the prover computes fractions h_i = eq(rho, i) / (alpha + w_i) and g_j = m_j / (alpha + t_j)
+// (these are the only places where inverses are needed), and claims S = sum_i h_i = sum_j g_j. Both sides are proven
+// by a single sumcheck each, batching the sum with the zerocheck of the defining relation:
+// sum_x eq(r, x) (h(x) (alpha + w(x)) + eq(rho, x)) + lambda h(x) = lambda S,
+// sum_y eq(r', y) (g(y) (alpha + t(y)) + m(y)) + lambda g(y) = lambda S.
+// The verifier evaluates eq and the table itself, and is left with claims about w, h (in one point) and m, g (in
+// another one), which are to be checked against commitments, i.e. using MulticlaimCheck and the PCS.
+
+// Protocol is split into phases, so the caller can commit to the intermediate polynomials: rho must be drawn after w is
+// committed, alpha after m is committed, and prove must be called after h and g are committed.
+
+use std::collections::HashMap;
+
+use num_traits::{One, Zero};
+#[cfg(feature = "parallel")]
+use rayon::{iter::{IndexedParallelIterator, ParallelIterator}, slice::{ParallelSlice, ParallelSliceMut}};
+
+use crate::{field::F128, traits::CompressedPoly, transcript::{prove_sumcheck, verify_sumcheck, Transcript}, utils::log2_exact};
+
+use super::{pipeline::EvalClaim, productcheck::{ProductSumcheck, ProductSumcheckOutput, ProductTerm}, utils::{eq_ev, eq_poly, evaluate}};
+
+const BATCH_INV_CHUNK: usize = 1 << 10;
+
+pub struct LogUp {
+    table: Vec<F128>,
+    index: HashMap<u128, usize>, // Position of a value in the table.
+    num_vars: usize,
+    table_vars: usize,
+}
+
+/// Polynomials computed by the prover during the protocol.
+pub struct LogUpWitness {
+    pub witness: Vec<F128>,
+    pub multiplicities: Vec<F128>,
+    pub witness_fractions: Vec<F128>,
+    pub table_fractions: Vec<F128>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogUpProof {
+    pub sum: F128,
+    pub witness_round_msgs: Vec<CompressedPoly>,
+    pub table_round_msgs: Vec<CompressedPoly>,
+    /// w(r), h(r)
+    pub witness_evals: [F128; 2],
+    /// m(r'), g(r')
+    pub table_evals: [F128; 2],
+}
+
+/// witness: claim about w, h; table: claim about m, g.
+#[derive(Clone, Debug)]
+pub struct LogUpClaims {
+    pub witness: EvalClaim,
+    pub table: EvalClaim,
+}
+
+impl LogUp {
+    /// Table entries must be distinct.
+    pub fn new(table: Vec<F128>, num_vars: usize) -> Self {
+        let table_vars = log2_exact(table.len());
+        let index : HashMap<u128, usize> = table.iter().enumerate().map(|(j, t)| (t.raw(), j)).collect();
+        assert!(index.len() == table.len(), "Table entries must be distinct.");
+        Self { table, index, num_vars, table_vars }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    pub fn table_vars(&self) -> usize {
+        self.table_vars
+    }
+
+    /// Phase 1: computes multiplicities, weighted by eq(rho, i).
+    pub fn multiplicities(&self, witness: &[F128], rho: &[F128]) -> Vec<F128> {
+        assert!(witness.len() == 1 << self.num_vars && rho.len() == self.num_vars);
+        let weights = eq_poly(rho);
+        let mut ret = vec![F128::zero(); self.table.len()];
+        for (w, weight) in witness.iter().zip(weights.iter()) {
+            let j = *self.index.get(&w.raw()).expect("Witness value is not in the table.");
+            ret[j] += *weight;
+        }
+        ret
+    }
+
+    /// Phase 2: computes fractions h_i = eq(rho, i) / (alpha + w_i) and g_j = m_j / (alpha + t_j).
+    pub fn fractions(&self, witness: Vec<F128>, multiplicities: Vec<F128>, rho: &[F128], alpha: F128) -> LogUpWitness {
+        assert!(multiplicities.len() == self.table.len());
+        let denoms : Vec<F128> = witness.iter().map(|w| alpha + w).collect();
+        let witness_fractions = batch_inv(&denoms).iter().zip(eq_poly(rho).iter()).map(|(d, e)| *d * e).collect();
+        let denoms : Vec<F128> = self.table.iter().map(|t| alpha + t).collect();
+        let table_fractions = batch_inv(&denoms).iter().zip(multiplicities.iter()).map(|(d, m)| *d * m).collect();
+        LogUpWitness { witness, multiplicities, witness_fractions, table_fractions }
+    }
+
+    /// Phase 3: proves that fractions are computed correctly and have equal sums.
+    pub fn prove(&self, wtns: &LogUpWitness, rho: &[F128], alpha: F128, transcript: &mut Transcript) -> (LogUpProof, LogUpClaims) {
+        let LogUpWitness { witness, multiplicities, witness_fractions, table_fractions } = wtns;
+        let sum = witness_fractions.iter().fold(F128::zero(), |a, b| a + b);
+        transcript.absorb(&[sum]);
+        let lambda = transcript.challenge();
+
+        let r = transcript.challenges(self.num_vars);
+        let mut prover = ProductSumcheck::new(
+            vec![eq_poly(&r), witness_fractions.clone(), witness.clone(), eq_poly(rho)],
+            fraction_terms(alpha, lambda),
+            lambda * sum,
+            false,
+        );
+        let (witness_round_msgs, rs) = prove_sumcheck(&mut prover, self.num_vars, transcript);
+        let ProductSumcheckOutput { evals } = prover.finish();
+        let witness_evals = [evals[2], evals[1]];
+        transcript.absorb(&witness_evals);
+
+        let r = transcript.challenges(self.table_vars);
+        let mut prover = ProductSumcheck::new(
+            vec![eq_poly(&r), table_fractions.clone(), self.table.clone(), multiplicities.clone()],
+            fraction_terms(alpha, lambda),
+            lambda * sum,
+            false,
+        );
+        let (table_round_msgs, rs_table) = prove_sumcheck(&mut prover, self.table_vars, transcript);
+        let ProductSumcheckOutput { evals } = prover.finish();
+        let table_evals = [evals[3], evals[1]];
+        transcript.absorb(&table_evals);
+
+        let claims = LogUpClaims {
+            witness: EvalClaim { point: rs, values: witness_evals.to_vec() },
+            table: EvalClaim { point: rs_table, values: table_evals.to_vec() },
+        };
+        (LogUpProof { sum, witness_round_msgs, table_round_msgs, witness_evals, table_evals }, claims)
+    }
+
+    /// Returns the claims about w, h and m, g, or None if verification fails.
+    pub fn verify(&self, rho: &[F128], alpha: F128, proof: &LogUpProof, transcript: &mut Transcript) -> Option<LogUpClaims> {
+        let LogUpProof { sum, witness_round_msgs, table_round_msgs, witness_evals, table_evals } = proof;
+        if witness_round_msgs.len() != self.num_vars || table_round_msgs.len() != self.table_vars {
+            return None;
+        }
+        transcript.absorb(&[*sum]);
+        let lambda = transcript.challenge();
+
+        let r = transcript.challenges(self.num_vars);
+        let (final_claim, rs) = verify_sumcheck(lambda * sum, witness_round_msgs, 3, transcript)?;
+        let [w, h] = *witness_evals;
+        if eq_ev(&r, &rs) * (h * (alpha + w) + eq_ev(rho, &rs)) + lambda * h != final_claim {
+            return None;
+        }
+        transcript.absorb(witness_evals);
+
+        let r = transcript.challenges(self.table_vars);
+        let (final_claim, rs_table) = verify_sumcheck(lambda * sum, table_round_msgs, 3, transcript)?;
+        let [m, g] = *table_evals;
+        if eq_ev(&r, &rs_table) * (g * (alpha + evaluate(&self.table, &rs_table)) + m) + lambda * g != final_claim {
+            return None;
+        }
+        transcript.absorb(table_evals);
+
+        Some(LogUpClaims {
+            witness: EvalClaim { point: rs, values: witness_evals.to_vec() },
+            table: EvalClaim { point: rs_table, values: table_evals.to_vec() },
+        })
+    }
+}
+
+/// Terms of eq * (f * (alpha + a) + b) + lambda * f, for polynomials [eq, f, a, b].
+fn fraction_terms(alpha: F128, lambda: F128) -> Vec<ProductTerm> {
+    vec![
+        ProductTerm::with_coeff(alpha, vec![0, 1]),
+        ProductTerm::new(vec![0, 1, 2]),
+        ProductTerm::new(vec![0, 3]),
+        ProductTerm::with_coeff(lambda, vec![1]),
+    ]
+}
+
+/// Inverts all values using Montgomery's trick (in chunks, which are processed in parallel).
+pub fn batch_inv(values: &[F128]) -> Vec<F128> {
+    let mut ret = vec![F128::zero(); values.len()];
+
+    let invert_chunk = |(src, dst): (&[F128], &mut [F128])| {
+        let mut acc = F128::one();
+        for (x, y) in src.iter().zip(dst.iter_mut()) {
+            *y = acc;
+            acc *= *x;
+        }
+        let mut inv = acc.inv();
+        for (x, y) in src.iter().zip(dst.iter_mut()).rev() {
+            *y *= inv;
+            inv *= *x;
+        }
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    values.chunks(BATCH_INV_CHUNK).zip(ret.chunks_mut(BATCH_INV_CHUNK)).for_each(invert_chunk);
+    #[cfg(feature = "parallel")]
+    values.par_chunks(BATCH_INV_CHUNK).zip(ret.par_chunks_mut(BATCH_INV_CHUNK)).for_each(invert_chunk);
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use rand::{rngs::OsRng, Rng};
+
+    use super::*;
+
+    #[test]
+    fn batch_inv_works() {
+        let rng = &mut OsRng;
+        let values : Vec<_> = repeat_with(|| F128::rand(rng)).take(3000).collect();
+        let inverses = batch_inv(&values);
+        assert!(values.iter().zip(inverses.iter()).all(|(x, y)| *x * y == F128::one()));
+    }
+
+    #[test]
+    fn byte_lookup_works() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+        let table : Vec<_> = (0..256).map(F128::from_raw).collect();
+        let witness : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::from_raw(rng.gen_range(0..256))).collect();
+
+        let lookup = LogUp::new(table, num_vars);
+
+        let prover_transcript = &mut Transcript::new(b"logup");
+        let rho = prover_transcript.challenges(num_vars);
+        let multiplicities = lookup.multiplicities(&witness, &rho);
+        let alpha = prover_transcript.challenge();
+        let wtns = lookup.fractions(witness.clone(), multiplicities, &rho, alpha);
+        let (mut proof, claims) = lookup.prove(&wtns, &rho, alpha, prover_transcript);
+
+        let verifier_transcript = &mut Transcript::new(b"logup");
+        let rho = verifier_transcript.challenges(num_vars);
+        let alpha = verifier_transcript.challenge();
+        let verified = lookup.verify(&rho, alpha, &proof, verifier_transcript).unwrap();
+        assert!(verified.witness.point == claims.witness.point && verified.witness.values == claims.witness.values);
+        assert!(verified.table.point == claims.table.point && verified.table.values == claims.table.values);
+
+        let EvalClaim { point, values } = claims.witness;
+        assert!(evaluate(&wtns.witness, &point) == values[0] && evaluate(&wtns.witness_fractions, &point) == values[1]);
+        let EvalClaim { point, values } = claims.table;
+        assert!(evaluate(&wtns.multiplicities, &point) == values[0] && evaluate(&wtns.table_fractions, &point) == values[1]);
+
+        proof.sum += F128::one();
+        let verifier_transcript = &mut Transcript::new(b"logup");
+        let rho = verifier_transcript.challenges(num_vars);
+        let alpha = verifier_transcript.challenge();
+        assert!(lookup.verify(&rho, alpha, &proof, verifier_transcript).is_none());
+    }
+
+    #[test]
+    fn repeated_value_outside_of_table_is_caught() {
+        let rng = &mut OsRng;
+        let num_vars = 4;
+        let table : Vec<_> = (0..16).map(F128::from_raw).collect();
+        let mut witness : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::from_raw(rng.gen_range(0..16))).collect();
+        // The value 100 appears twice, which would cancel out in the unweighted LogUp identity.
+        witness[3] = F128::from_raw(100);
+        witness[7] = F128::from_raw(100);
+
+        let lookup = LogUp::new(table, num_vars);
+        let transcript = &mut Transcript::new(b"logup");
+        let rho = transcript.challenges(num_vars);
+        let alpha = transcript.challenge();
+
+        // Cheating prover ignores values outside of the table when computing multiplicities.
+        let weights = eq_poly(&rho);
+        let mut multiplicities = vec![F128::zero(); 16];
+        for (w, e) in witness.iter().zip(weights.iter()) {
+            if w.raw() < 16 {
+                multiplicities[w.raw() as usize] += *e;
+            }
+        }
+        let wtns = lookup.fractions(witness, multiplicities, &rho, alpha);
+        let witness_sum = wtns.witness_fractions.iter().fold(F128::zero(), |a, b| a + b);
+        let table_sum = wtns.table_fractions.iter().fold(F128::zero(), |a, b| a + b);
+        assert!(witness_sum != table_sum);
+    }
+}
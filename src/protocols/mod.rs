@@ -1,5 +1,17 @@
 pub mod prodcheck;
+pub mod productcheck;
+pub mod batched;
 pub mod boolcheck;
+pub mod composite;
+pub mod grandproduct;
+pub mod linop;
+pub mod logup;
+pub mod layered;
 pub mod lincheck;
 pub mod multiclaim;
-pub mod utils;
\ No newline at end of file
+pub mod permutation;
+pub mod pipeline;
+pub mod shift;
+pub mod subfield;
+pub mod utils;
+pub mod zk;
\ No newline at end of file
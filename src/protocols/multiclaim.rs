@@ -14,7 +14,7 @@ use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIter
 
 use crate::{field::F128, precompute::frobenius_table::FROBENIUS, protocols::utils::frobenius_inv_lc, traits::{CompressedPoly, SumcheckObject}};
 
-use super::{prodcheck::Prodcheck, utils::{eq_poly, evaluate, evaluate_univar}};
+use super::{prodcheck::Prodcheck, utils::{eq_ev, eq_poly, evaluate, evaluate_univar}};
 
 pub struct MulticlaimCheck<'a, const N: usize> {
     polys: &'a [Vec<F128>; N],
@@ -34,9 +34,10 @@ impl<'a, const N: usize> MulticlaimCheck<'a, N> {
     pub fn folding_challenge(self, gamma: F128) -> MulticlaimCheckSingle<'a, N> {
         let Self { polys, pt, openings } = self;
     
-        let mut gamma_pows = Vec::with_capacity(128 * N);
+        // One extra power, gamma^128 is needed to combine the openings in the end (even if N == 1).
+        let mut gamma_pows = Vec::with_capacity(128 * N + 1);
         let mut tmp = F128::one();
-        for i in 0..128*N {
+        for i in 0..128*N + 1 {
             gamma_pows.push(tmp);
             tmp *= gamma;
         }
@@ -92,7 +93,7 @@ impl<'a, const N: usize> MulticlaimCheckSingle<'a, N> {
                 *initial_claim,
                 false,
                 false
-            ),
+            ).with_fused_bind(),
             polys,
             gamma128 : gamma_pows[128],
         }
@@ -108,6 +109,28 @@ impl<'a, const N: usize> MulticlaimCheckSingle<'a, N> {
 
 }
 
+/// Verifier side: computes the expected final claim of MulticlaimCheck from the openings returned by the prover,
+/// the initial point pt, challenges rs and the folding challenge gamma.
+pub fn multiclaim_final_claim(pt: &[F128], rs: &[F128], gamma: F128, openings: &[F128]) -> F128 {
+    // Openings were claimed in the points of the inverse Frobenius orbit of pt, i.e. Frob^{-i}(pt).
+    let mut pt_inv_orbit = vec![];
+    let mut tmp = pt.to_vec();
+    for _ in 0..128 {
+        tmp.iter_mut().map(|x| *x *= *x).count();
+        pt_inv_orbit.push(tmp.clone());
+    }
+    pt_inv_orbit.reverse();
+
+    let eq_evs : Vec<_> = pt_inv_orbit.iter().map(|pt| eq_ev(pt, rs)).collect();
+
+    let mut gamma128 = gamma;
+    for _ in 0..7 {
+        gamma128 *= gamma128;
+    }
+
+    evaluate_univar(openings, gamma128) * evaluate_univar(&eq_evs, gamma)
+}
+
 impl<'a, const N: usize> SumcheckObject for MulticlaimCheckSingle<'a, N> {
     fn is_reverse_order(&self) -> bool {
         self.object.is_reverse_order()
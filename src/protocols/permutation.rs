@@ -0,0 +1,231 @@
+// Multiset equality and permutation (copy constraint) checks, based on the grand-product argument.
+
+// To prove that multisets {(a_i, l_i)} and {(b_i, l'_i)}, taken over all lhs and rhs columns, are equal, the
+// verifier sends random beta and gamma, and the prover shows that
+// prod (gamma + a_i + beta * l_i) = prod (gamma + b_i + beta * l'_i).
+// Labels are public polynomials, which the verifier evaluates itself. Without labels, this proves that rhs columns
+// are a permutation of lhs columns. Copy constraints are obtained by using the same columns on both sides, labeled by
+// positions on the left and by positions permuted by sigma on the right.
+// Every column gets its own grand product, so the check also wires together separately committed segments.
+// The result is a collection of evaluation claims about the columns, which need to be checked against commitments.
+// Commitments to the columns must be absorbed into the transcript before the check, as beta and gamma are drawn from it.
+
+use num_traits::{One, Zero};
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{field::F128, transcript::Transcript};
+
+use super::{grandproduct::{verify_grand_product, GrandProduct, GrandProductProof}, pipeline::EvalClaim, utils::evaluate};
+
+/// Public label of a column.
+#[derive(Clone, Debug)]
+pub enum Label {
+    None,
+    /// Label of i-th element of segment s is the position (s << num_vars) + i, interpreted as an element of F128.
+    Position(usize),
+    Public(Vec<F128>),
+}
+
+impl Label {
+    pub fn values(&self, num_vars: usize) -> Vec<F128> {
+        match self {
+            Label::None => vec![F128::zero(); 1 << num_vars],
+            Label::Position(s) => (0 .. 1 << num_vars).map(|i| position_label(*s, num_vars, i)).collect(),
+            Label::Public(values) => values.clone(),
+        }
+    }
+
+    /// Evaluates the multilinear extension of the label.
+    pub fn evaluate(&self, pt: &[F128]) -> F128 {
+        match self {
+            Label::None => F128::zero(),
+            // Position is linear over F2 in the bits of i, so its extension is affine.
+            Label::Position(s) => pt.iter().enumerate()
+                .fold(F128::from_raw((*s as u128) << pt.len()), |acc, (j, x)| acc + F128::basis(j) * x),
+            Label::Public(values) => evaluate(values, pt),
+        }
+    }
+}
+
+pub fn position_label(segment: usize, num_vars: usize, i: usize) -> F128 {
+    F128::from_raw(((segment << num_vars) + i) as u128)
+}
+
+pub struct MultisetCheck {
+    num_vars: usize,
+    lhs: Vec<Label>,
+    rhs: Vec<Label>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MultisetProof {
+    pub lhs_products: Vec<F128>,
+    pub rhs_products: Vec<F128>,
+    pub lhs_proofs: Vec<GrandProductProof>,
+    pub rhs_proofs: Vec<GrandProductProof>,
+}
+
+impl MultisetCheck {
+    pub fn new(num_vars: usize, lhs: Vec<Label>, rhs: Vec<Label>) -> Self {
+        for label in lhs.iter().chain(rhs.iter()) {
+            if let Label::Public(values) = label {
+                assert!(values.len() == 1 << num_vars);
+            }
+        }
+        Self { num_vars, lhs, rhs }
+    }
+
+    /// Checks that rhs columns are a permutation of lhs columns.
+    pub fn unlabeled(num_vars: usize, num_lhs: usize, num_rhs: usize) -> Self {
+        Self::new(num_vars, vec![Label::None; num_lhs], vec![Label::None; num_rhs])
+    }
+
+    /// Copy constraints: sigma[s][i] = (s', i') means that the i-th element of segment s must be equal to i'-th element
+    /// of segment s'. Here sigma must be a permutation of all positions, and the same columns must be passed as lhs
+    /// and rhs.
+    pub fn copy_constraints(num_vars: usize, sigma: &[Vec<(usize, usize)>]) -> Self {
+        let lhs = (0..sigma.len()).map(Label::Position).collect();
+        let rhs = sigma.iter().map(|segment| {
+            assert!(segment.len() == 1 << num_vars);
+            Label::Public(segment.iter().map(|&(s, i)| position_label(s, num_vars, i)).collect())
+        }).collect();
+        Self::new(num_vars, lhs, rhs)
+    }
+
+    fn fingerprint(&self, column: &[F128], label: &Label, beta: F128, gamma: F128) -> Vec<F128> {
+        assert!(column.len() == 1 << self.num_vars);
+        let labels = label.values(self.num_vars);
+
+        #[cfg(not(feature = "parallel"))]
+        let ret = (0..column.len()).map(|i| gamma + column[i] + beta * labels[i]).collect();
+        #[cfg(feature = "parallel")]
+        let ret = (0..column.len()).into_par_iter().map(|i| gamma + column[i] + beta * labels[i]).collect();
+
+        ret
+    }
+
+    /// Returns the proof, and the claims about lhs and rhs columns.
+    pub fn prove(&self, lhs: &[Vec<F128>], rhs: &[Vec<F128>], transcript: &mut Transcript) -> (MultisetProof, Vec<EvalClaim>, Vec<EvalClaim>) {
+        assert!(lhs.len() == self.lhs.len() && rhs.len() == self.rhs.len());
+        let beta = transcript.challenge();
+        let gamma = transcript.challenge();
+
+        let mut prove_side = |columns: &[Vec<F128>], labels: &[Label]| {
+            let mut products = vec![];
+            let mut proofs = vec![];
+            let mut claims = vec![];
+            for (column, label) in columns.iter().zip(labels.iter()) {
+                let gp = GrandProduct::new(self.fingerprint(column, label, beta, gamma));
+                let (proof, claim) = gp.prove(transcript);
+                products.push(gp.product());
+                proofs.push(proof);
+                claims.push(self.unfingerprint(claim, label, beta, gamma));
+            }
+            (products, proofs, claims)
+        };
+
+        let (lhs_products, lhs_proofs, lhs_claims) = prove_side(lhs, &self.lhs);
+        let (rhs_products, rhs_proofs, rhs_claims) = prove_side(rhs, &self.rhs);
+
+        (MultisetProof { lhs_products, rhs_products, lhs_proofs, rhs_proofs }, lhs_claims, rhs_claims)
+    }
+
+    /// Converts the claim about the fingerprint into the claim about the column.
+    fn unfingerprint(&self, claim: EvalClaim, label: &Label, beta: F128, gamma: F128) -> EvalClaim {
+        let EvalClaim { point, values } = claim;
+        let value = values[0] + gamma + beta * label.evaluate(&point);
+        EvalClaim { point, values: vec![value] }
+    }
+
+    /// Returns the claims about lhs and rhs columns, or None if verification fails.
+    pub fn verify(&self, proof: &MultisetProof, transcript: &mut Transcript) -> Option<(Vec<EvalClaim>, Vec<EvalClaim>)> {
+        let MultisetProof { lhs_products, rhs_products, lhs_proofs, rhs_proofs } = proof;
+        if lhs_products.len() != self.lhs.len() || lhs_proofs.len() != self.lhs.len()
+            || rhs_products.len() != self.rhs.len() || rhs_proofs.len() != self.rhs.len() {
+            return None;
+        }
+        let total = |products: &[F128]| products.iter().fold(F128::one(), |acc, x| acc * x);
+        if total(lhs_products) != total(rhs_products) {
+            return None;
+        }
+
+        let beta = transcript.challenge();
+        let gamma = transcript.challenge();
+
+        let mut verify_side = |products: &[F128], proofs: &[GrandProductProof], labels: &[Label]| {
+            products.iter().zip(proofs.iter()).zip(labels.iter()).map(|((product, proof), label)| {
+                verify_grand_product(self.num_vars, *product, proof, transcript)
+                    .map(|claim| self.unfingerprint(claim, label, beta, gamma))
+            }).collect::<Option<Vec<_>>>()
+        };
+
+        let lhs_claims = verify_side(lhs_products, lhs_proofs, &self.lhs)?;
+        let rhs_claims = verify_side(rhs_products, rhs_proofs, &self.rhs)?;
+        Some((lhs_claims, rhs_claims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use rand::{rngs::OsRng, seq::SliceRandom};
+
+    use super::*;
+
+    fn check_claims(columns: &[Vec<F128>], claims: &[EvalClaim]) {
+        for (column, claim) in columns.iter().zip(claims.iter()) {
+            assert!(evaluate(column, &claim.point) == claim.values[0]);
+        }
+    }
+
+    #[test]
+    fn multiset_equality_works() {
+        let rng = &mut OsRng;
+        let num_vars = 8;
+        let lhs : Vec<Vec<F128>> = (0..2).map(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect()).collect();
+
+        // Shuffle all elements between two columns.
+        let mut all : Vec<F128> = lhs.concat();
+        all.shuffle(rng);
+        let rhs : Vec<Vec<F128>> = all.chunks(1 << num_vars).map(|c| c.to_vec()).collect();
+
+        let check = MultisetCheck::unlabeled(num_vars, 2, 2);
+        let (proof, lhs_claims, rhs_claims) = check.prove(&lhs, &rhs, &mut Transcript::new(b"multiset"));
+        let (v_lhs, v_rhs) = check.verify(&proof, &mut Transcript::new(b"multiset")).unwrap();
+        check_claims(&lhs, &lhs_claims);
+        check_claims(&rhs, &rhs_claims);
+        assert!(v_lhs.iter().zip(lhs_claims.iter()).all(|(a, b)| a.point == b.point && a.values == b.values));
+        assert!(v_rhs.iter().zip(rhs_claims.iter()).all(|(a, b)| a.point == b.point && a.values == b.values));
+
+        let mut wrong_rhs = rhs.clone();
+        wrong_rhs[1][3] = F128::rand(rng);
+        let (proof, _, _) = check.prove(&lhs, &wrong_rhs, &mut Transcript::new(b"multiset"));
+        assert!(check.verify(&proof, &mut Transcript::new(b"multiset")).is_none());
+    }
+
+    #[test]
+    fn copy_constraints_work() {
+        let rng = &mut OsRng;
+        let num_vars = 6;
+        let mut segments : Vec<Vec<F128>> = (0..2).map(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect()).collect();
+
+        // Last element of segment 0 must be equal to the first element of segment 1, the rest is unconstrained.
+        let last = (1 << num_vars) - 1;
+        let mut sigma : Vec<Vec<(usize, usize)>> = (0..2).map(|s| (0 .. 1 << num_vars).map(|i| (s, i)).collect()).collect();
+        sigma[0][last] = (1, 0);
+        sigma[1][0] = (0, last);
+        segments[1][0] = segments[0][last];
+
+        let check = MultisetCheck::copy_constraints(num_vars, &sigma);
+        let (proof, lhs_claims, rhs_claims) = check.prove(&segments, &segments, &mut Transcript::new(b"copy"));
+        assert!(check.verify(&proof, &mut Transcript::new(b"copy")).is_some());
+        check_claims(&segments, &lhs_claims);
+        check_claims(&segments, &rhs_claims);
+
+        segments[1][0] = F128::rand(rng);
+        let (proof, _, _) = check.prove(&segments, &segments, &mut Transcript::new(b"copy"));
+        assert!(check.verify(&proof, &mut Transcript::new(b"copy")).is_none());
+    }
+}
@@ -0,0 +1,243 @@
+// Typed claims and a pipeline builder for chaining protocols.
+
+// Every protocol in a layered proof consumes a claim about the outputs of its layer, and emits a claim about its
+// inputs. Stage trait captures this, and allows to write the proof as a chain
+// BoolCheckStage -> MulticlaimStage -> LincheckStage -> ...
+// Currently, stages are interactive (only useful for testing): each stage runs the prover together with the
+// verifier, drawing challenges from the provided rng, and panics if verification fails.
+
+use rand::Rng;
+
+use crate::{commitment::PolynomialCommitment, field::F128, traits::SumcheckObject};
+
+use super::{
+    boolcheck::{boolcheck_final_claim, boolcheck_initial_claim, BoolCheck, BoolCheckOutput, FnPackage},
+    lincheck::{lincheck_final_claim, LinOp, Lincheck, LincheckOutput},
+    multiclaim::{multiclaim_final_claim, MulticlaimCheck},
+    shift::{shift_final_claim, ShiftCheck},
+    utils::evaluate_univar,
+};
+
+/// Claim that values[i] = P_i(point).
+#[derive(Clone, Debug)]
+pub struct EvalClaim {
+    pub point: Vec<F128>,
+    pub values: Vec<F128>,
+}
+
+/// Claim about the evaluations of P_i in the inverse Frobenius orbit of the point, in the twisted form in which
+/// they are returned by BoolCheck (chunks of 128 per polynomial).
+#[derive(Clone, Debug)]
+pub struct OrbitClaim {
+    pub point: Vec<F128>,
+    pub frob_evals: Vec<F128>,
+}
+
+pub trait Stage {
+    type Input;
+    type Output;
+
+    /// Runs the prover and the verifier of this stage, returns the reduced claim.
+    fn run<RNG: Rng>(self, claim: Self::Input, rng: &mut RNG) -> Self::Output;
+
+    /// Chains this stage with the next one, which consumes its output claim.
+    fn then<B: Stage<Input = Self::Output>>(self, next: B) -> Chain<Self, B> where Self: Sized {
+        Chain { first: self, second: next }
+    }
+}
+
+pub struct Chain<A: Stage, B: Stage<Input = A::Output>> {
+    first: A,
+    second: B,
+}
+
+impl<A: Stage, B: Stage<Input = A::Output>> Stage for Chain<A, B> {
+    type Input = A::Input;
+    type Output = B::Output;
+
+    fn run<RNG: Rng>(self, claim: Self::Input, rng: &mut RNG) -> Self::Output {
+        let claim = self.first.run(claim, rng);
+        self.second.run(claim, rng)
+    }
+}
+
+/// Runs sumcheck rounds, checking degrees of round polynomials. Returns the final claim and the challenges.
+pub fn run_sumcheck<S: SumcheckObject, RNG: Rng>(
+    object: &mut S,
+    mut claim: F128,
+    num_rounds: usize,
+    degree: usize,
+    rng: &mut RNG,
+) -> (F128, Vec<F128>) {
+    let mut rs = Vec::with_capacity(num_rounds);
+    for _ in 0..num_rounds {
+        let rpoly = object.round_msg();
+        let r = F128::rand(rng);
+        claim = rpoly.evaluate_with_degree(claim, r, degree);
+        object.bind(r);
+        rs.push(r);
+    }
+    (claim, rs)
+}
+
+/// Reduces a claim about outputs of f (applied to the inputs polys) to the claim about evaluations of inputs in
+/// the Frobenius orbit of a new point.
+pub struct BoolCheckStage<const N: usize, const M: usize, F: FnPackage<N, M>> {
+    f: F,
+    polys: [Vec<F128>; N],
+    c: usize,
+}
+
+impl<const N: usize, const M: usize, F: FnPackage<N, M>> BoolCheckStage<N, M, F> {
+    pub fn new(f: F, polys: [Vec<F128>; N], c: usize) -> Self {
+        Self { f, polys, c }
+    }
+}
+
+impl<const N: usize, const M: usize, F: FnPackage<N, M>> Stage for BoolCheckStage<N, M, F> {
+    type Input = EvalClaim;
+    type Output = OrbitClaim;
+
+    fn run<RNG: Rng>(self, claim: EvalClaim, rng: &mut RNG) -> OrbitClaim {
+        let Self { f, polys, c } = self;
+        let EvalClaim { point, values } = claim;
+        let evaluation_claims : [F128; M] = values.try_into().unwrap();
+        let num_vars = point.len();
+
+        let gamma = F128::rand(rng);
+        let mut prover = BoolCheck::new(&f, polys, c, evaluation_claims, point.clone()).folding_challenge(gamma);
+        let initial_claim = boolcheck_initial_claim(&f, &point, &evaluation_claims, gamma);
+        let (final_claim, rs) = run_sumcheck(&mut prover, initial_claim, num_vars, 3, rng);
+        let BoolCheckOutput { frob_evals, .. } = prover.finish();
+
+        assert!(boolcheck_final_claim(&f, &point, &rs, gamma, &frob_evals) == final_claim, "BoolCheck final check failed.");
+        OrbitClaim { point: rs, frob_evals }
+    }
+}
+
+/// Reduces evaluations in the Frobenius orbit to evaluations in a single new point.
+pub struct MulticlaimStage<'a, const N: usize> {
+    polys: &'a [Vec<F128>; N],
+}
+
+impl<'a, const N: usize> MulticlaimStage<'a, N> {
+    pub fn new(polys: &'a [Vec<F128>; N]) -> Self {
+        Self { polys }
+    }
+}
+
+impl<'a, const N: usize> Stage for MulticlaimStage<'a, N> {
+    type Input = OrbitClaim;
+    type Output = EvalClaim;
+
+    fn run<RNG: Rng>(self, claim: OrbitClaim, rng: &mut RNG) -> EvalClaim {
+        let OrbitClaim { point, frob_evals } = claim;
+        let num_vars = point.len();
+
+        let gamma = F128::rand(rng);
+        let mut prover = MulticlaimCheck::new(self.polys, point.clone(), frob_evals.clone()).folding_challenge(gamma);
+        let (final_claim, rs) = run_sumcheck(&mut prover, evaluate_univar(&frob_evals, gamma), num_vars, 2, rng);
+        let values = prover.finish();
+
+        assert!(multiclaim_final_claim(&point, &rs, gamma, &values) == final_claim, "Multiclaim final check failed.");
+        EvalClaim { point: rs, values }
+    }
+}
+
+/// Reduces a claim about outputs of a linear map (acting on num_active_vars first variables) to a claim about its
+/// inputs.
+pub struct LincheckStage<'a, const N: usize, const M: usize, L: LinOp> {
+    polys: &'a [Vec<F128>; N],
+    matrix: L,
+    num_active_vars: usize,
+}
+
+impl<'a, const N: usize, const M: usize, L: LinOp> LincheckStage<'a, N, M, L> {
+    pub fn new(polys: &'a [Vec<F128>; N], matrix: L, num_active_vars: usize) -> Self {
+        Self { polys, matrix, num_active_vars }
+    }
+}
+
+impl<'a, const N: usize, const M: usize, L: LinOp> Stage for LincheckStage<'a, N, M, L> {
+    type Input = EvalClaim;
+    type Output = EvalClaim;
+
+    fn run<RNG: Rng>(self, claim: EvalClaim, rng: &mut RNG) -> EvalClaim {
+        let Self { polys, matrix, num_active_vars } = self;
+        let EvalClaim { point, values } = claim;
+        let initial_claims : [F128; M] = values.try_into().unwrap();
+        let active_vars : Vec<_> = (0..num_active_vars).collect();
+
+        let gamma = F128::rand(rng);
+        let mut prover = Lincheck::new_borrowed(polys, point.clone(), &matrix, num_active_vars, initial_claims).folding_challenge(gamma);
+        let (final_claim, rs) = run_sumcheck(&mut prover, evaluate_univar(&initial_claims, gamma), num_active_vars, 2, rng);
+        let new_point = prover.output_points().pop().unwrap();
+        let LincheckOutput { p_evs, .. } = prover.finish();
+
+        assert!(lincheck_final_claim(&matrix, &point, &active_vars, &rs, gamma, &p_evs) == final_claim, "Lincheck final check failed.");
+        EvalClaim { point: new_point, values: p_evs }
+    }
+}
+
+/// Reduces a claim about shift_k(P_i) (shifted inside of blocks of size 2^block_vars) to a claim about P_i. This wires
+/// outputs of one round to inputs of the next one, if they are laid out differently.
+pub struct ShiftStage<const N: usize> {
+    polys: [Vec<F128>; N],
+    block_vars: usize,
+    k: usize,
+}
+
+impl<const N: usize> ShiftStage<N> {
+    pub fn new(polys: [Vec<F128>; N], block_vars: usize, k: usize) -> Self {
+        Self { polys, block_vars, k }
+    }
+}
+
+impl<const N: usize> Stage for ShiftStage<N> {
+    type Input = EvalClaim;
+    type Output = EvalClaim;
+
+    fn run<RNG: Rng>(self, claim: EvalClaim, rng: &mut RNG) -> EvalClaim {
+        let Self { polys, block_vars, k } = self;
+        let EvalClaim { point, values } = claim;
+        let initial_claims : [F128; N] = values.try_into().unwrap();
+        let num_vars = point.len();
+
+        let gamma = F128::rand(rng);
+        let mut prover = ShiftCheck::new(polys, point.clone(), block_vars, k, initial_claims).folding_challenge(gamma);
+        let (final_claim, rs) = run_sumcheck(&mut prover, evaluate_univar(&initial_claims, gamma), num_vars, 2, rng);
+        let values = prover.finish();
+
+        assert!(shift_final_claim(&point, block_vars, k, &rs, gamma, &values) == final_claim, "Shift final check failed.");
+        EvalClaim { point: rs, values }
+    }
+}
+
+/// Final stage, which checks the claim against commitments of the input polynomials. It binds the prover to
+/// the committed input.
+pub struct OpeningStage<'a, P: PolynomialCommitment> {
+    pcs: &'a P,
+    commitments: Vec<P::Commitment>,
+    prover_data: Vec<&'a P::ProverData>,
+}
+
+impl<'a, P: PolynomialCommitment> OpeningStage<'a, P> {
+    pub fn new(pcs: &'a P, commitments: Vec<P::Commitment>, prover_data: Vec<&'a P::ProverData>) -> Self {
+        assert!(commitments.len() == prover_data.len());
+        Self { pcs, commitments, prover_data }
+    }
+}
+
+impl<'a, P: PolynomialCommitment> Stage for OpeningStage<'a, P> {
+    type Input = EvalClaim;
+    type Output = ();
+
+    fn run<RNG: Rng>(self, claim: EvalClaim, _rng: &mut RNG) {
+        let EvalClaim { point, values } = claim;
+        assert!(values.len() == self.commitments.len());
+        for i in 0..values.len() {
+            let proof = self.pcs.open(self.prover_data[i], &point);
+            assert!(self.pcs.verify(&self.commitments[i], &point, values[i], &proof), "Opening of polynomial {} failed.", i);
+        }
+    }
+}
@@ -5,7 +5,7 @@ use itertools::Itertools;
 use num_traits::Zero;
 use rayon::iter::{ParallelIterator, IntoParallelIterator};
 
-use crate::{field::F128, traits::{CompressedPoly, SumcheckObject}, utils::log2_exact};
+use crate::{field::F128, ptr_utils::{AsSharedMutPtr, MutPtr, UnsafeIndexRaw, UnsafeIndexRawMut}, traits::{CompressedPoly, SumcheckObject}, utils::log2_exact};
 
 
 /// A very simple sumcheck, only does product of 2 polynomials. It is used as main component for lincheck.
@@ -21,6 +21,7 @@ pub struct Prodcheck {
     // cached_q_bind: Option<Vec<F128>>,
 
     rev_order: bool, 
+    fused_bind: bool,
 }
 
 impl Prodcheck {
@@ -62,9 +63,17 @@ impl Prodcheck {
             // cached_q_bind: None,
             cached_round_msg: None,
             rev_order: in_reverse_order,
+            fused_bind: false,
         }
     }
 
+    /// Computes the next round message in the same pass over the data as binding, instead of making a separate
+    /// pass in round_msg.
+    pub fn with_fused_bind(mut self) -> Self {
+        self.fused_bind = true;
+        self
+    }
+
     pub fn finish(self) -> ProdcheckOutput {
         let p_evs = self.p_polys.iter().map(|poly| {assert!(poly.len() == 1); poly[0]}).collect();
         let q_evs = self.q_polys.iter().map(|poly| {assert!(poly.len() == 1); poly[0]}).collect();
@@ -89,45 +98,51 @@ impl SumcheckObject for Prodcheck {
         }
         assert!(self.p_polys[0].len() > 1, "The protocol has already ended.");
         let half = self.p_polys[0].len() / 2;
-        let l = self.p_polys.len();
 
         let round_poly = self.round_msg().coeffs(self.claim);
         // Decompressed round polynomial in a coefficient form.
         self.claim = round_poly[0] + challenge * round_poly[1] + challenge * challenge * round_poly[2];
         self.challenges.push(challenge);
 
+        // Next round message can only be computed if there is a next round.
+        let fuse = self.fused_bind && half > 1;
+
+        let p_ptrs : Vec<_> = self.p_polys.iter_mut().map(|p| p.as_shared_mut_ptr()).collect();
+        let q_ptrs : Vec<_> = self.q_polys.iter_mut().map(|q| q.as_shared_mut_ptr()).collect();
+
         #[cfg(not(feature = "parallel"))]
-        {
-            for i in 0..l {
-                for j in 0..half {
-                    self.p_polys[i][j] = self.p_polys[i][2 * j] + (self.p_polys[i][2 * j + 1] + self.p_polys[i][2 * j]) * challenge;
-                    self.q_polys[i][j] = self.q_polys[i][2 * j] + (self.q_polys[i][2 * j + 1] + self.q_polys[i][2 * j]) * challenge;
-                    self.p_polys[i].truncate(half);
-                    self.q_polys[i].truncate(half);
-                }
-            }
-        }
+        let response = unsafe{ bind_block(&p_ptrs, &q_ptrs, 0, half, challenge, fuse) };
 
         #[cfg(feature = "parallel")]
-        {
-            let mut p_new = vec![];
-            let mut q_new = vec![];
-
-            for i in 0..l {
-                p_new.push((0..half).into_par_iter().map(|j| {
-                    self.p_polys[i][2 * j] + (self.p_polys[i][2 * j + 1] + self.p_polys[i][2 * j]) * challenge
-                }).collect());
-                q_new.push((0..half).into_par_iter().map(|j| {
-                    self.q_polys[i][2 * j] + (self.q_polys[i][2 * j + 1] + self.q_polys[i][2 * j]) * challenge
-                }).collect());
+        let response = if half <= BIND_BLOCK_SIZE {
+            unsafe{ bind_block(&p_ptrs, &q_ptrs, 0, half, challenge, fuse) }
+        } else {
+            // Block c reads [2c * B, 2(c+1) * B) and writes [c * B, (c+1) * B). The region it writes was read by
+            // block c / 2, and the region it reads is only written by blocks 2c and 2c + 1. So blocks are processed
+            // in levels [s, 2s), each level only overwriting data already consumed by the previous levels.
+            let num_blocks = half / BIND_BLOCK_SIZE;
+            let mut response = unsafe{ bind_block(&p_ptrs, &q_ptrs, 0, BIND_BLOCK_SIZE, challenge, fuse) };
+            let mut s = 1;
+            while s < num_blocks {
+                let level_response = (s .. 2 * s).into_par_iter().map(|c| {
+                    unsafe{ bind_block(&p_ptrs, &q_ptrs, c * BIND_BLOCK_SIZE, BIND_BLOCK_SIZE, challenge, fuse) }
+                }).reduce(|| [F128::zero(), F128::zero(), F128::zero()], |[a, b, c], [d, e, f]| [a+d, b+e, c+f]);
+                response = add_evals(response, level_response);
+                s *= 2;
             }
-            
-            self.p_polys = p_new;
-            self.q_polys = q_new;
-            
+            response
+        };
+
+        for i in 0..self.p_polys.len() {
+            self.p_polys[i].truncate(half);
+            self.q_polys[i].truncate(half);
         }
 
-        self.cached_round_msg = None;
+        self.cached_round_msg = if fuse {
+            Some(compress_evals(response))
+        } else {
+            None
+        };
     }
 
     fn round_msg(&mut self) -> CompressedPoly {
@@ -177,25 +192,72 @@ impl SumcheckObject for Prodcheck {
         });
         
         #[cfg(not(feature = "parallel"))]
-        let mut response = iter.fold([F128::zero(), F128::zero(), F128::zero()], |[a, b, c], [d, e, f]| [a+d, b+e, c+f]);
+        let response = iter.fold([F128::zero(), F128::zero(), F128::zero()], |[a, b, c], [d, e, f]| [a+d, b+e, c+f]);
 
         #[cfg(feature = "parallel")]
-        let mut response = iter.reduce(|| [F128::zero(), F128::zero(), F128::zero()], |[a, b, c], [d, e, f]| [a+d, b+e, c+f]);
-
-        // let acc : [u64; 6] = acc.iter().map(|x| x.load(Ordering::Relaxed)).collect_vec().try_into().unwrap();
-        // let mut response = cast::<[u64; 6], [F128; 3]>(acc);
+        let response = iter.reduce(|| [F128::zero(), F128::zero(), F128::zero()], |[a, b, c], [d, e, f]| [a+d, b+e, c+f]);
 
-        // cast to coefficient form
-        response[1] += response[0];
-        response[1] += response[2];
-
-        let (compressed_response, _) = CompressedPoly::compress(&response);
+        let compressed_response = compress_evals(response);
 
         self.cached_round_msg = Some(compressed_response.clone());
         compressed_response
     }
 }
 
+/// Size of the output block processed by a single task during in-place binding.
+const BIND_BLOCK_SIZE: usize = 1 << 12;
+
+fn add_evals([a, b, c]: [F128; 3], [d, e, f]: [F128; 3]) -> [F128; 3] {
+    [a + d, b + e, c + f]
+}
+
+/// Converts evaluations in 0, 1, inf to the compressed coefficient form.
+fn compress_evals(mut response: [F128; 3]) -> CompressedPoly {
+    response[1] += response[0];
+    response[1] += response[2];
+    CompressedPoly::compress(&response).0
+}
+
+/// Binds the lowest variable of all polynomials in place, writing outputs [start, start + len) from the inputs
+/// [2 * start, 2 * (start + len)). If fuse is set, also returns evaluations in 0, 1, inf of the next round polynomial
+/// restricted to this block (start and len must then be even).
+/// Safety: pointers must be valid for 2 * (start + len) elements, and no other thread may access the touched ranges.
+unsafe fn bind_block(
+    p_ptrs: &[MutPtr<F128>],
+    q_ptrs: &[MutPtr<F128>],
+    start: usize,
+    len: usize,
+    challenge: F128,
+    fuse: bool,
+) -> [F128; 3] {
+    let mut response = [F128::zero(), F128::zero(), F128::zero()];
+    if !fuse {
+        for (p, q) in p_ptrs.iter().zip(q_ptrs.iter()) {
+            for j in start .. start + len {
+                *p.get_mut(j) = *p.get(2 * j) + (*p.get(2 * j + 1) + *p.get(2 * j)) * challenge;
+                *q.get_mut(j) = *q.get(2 * j) + (*q.get(2 * j + 1) + *q.get(2 * j)) * challenge;
+            }
+        }
+        return response;
+    }
+    for (p, q) in p_ptrs.iter().zip(q_ptrs.iter()) {
+        for j in (start .. start + len).step_by(2) {
+            let p0 = *p.get(2 * j) + (*p.get(2 * j + 1) + *p.get(2 * j)) * challenge;
+            let p1 = *p.get(2 * j + 2) + (*p.get(2 * j + 3) + *p.get(2 * j + 2)) * challenge;
+            let q0 = *q.get(2 * j) + (*q.get(2 * j + 1) + *q.get(2 * j)) * challenge;
+            let q1 = *q.get(2 * j + 2) + (*q.get(2 * j + 3) + *q.get(2 * j + 2)) * challenge;
+            *p.get_mut(j) = p0;
+            *p.get_mut(j + 1) = p1;
+            *q.get_mut(j) = q0;
+            *q.get_mut(j + 1) = q1;
+            response[0] += p0 * q0;
+            response[1] += p1 * q1;
+            response[2] += (p0 + p1) * (q0 + q1);
+        }
+    }
+    response
+}
+
 #[cfg(test)]
 mod tests {
     use rand::rngs::OsRng;
@@ -206,6 +268,15 @@ mod tests {
 
     #[test]
     fn prodcheck_works() {
+        run_prodcheck(false);
+    }
+
+    #[test]
+    fn prodcheck_fused_bind_works() {
+        run_prodcheck(true);
+    }
+
+    fn run_prodcheck(fused: bool) {
         let rng = &mut OsRng;
         let num_vars = 15;
 
@@ -220,6 +291,9 @@ mod tests {
         let mut claim = p_polys.iter().flatten().zip(q_polys.iter().flatten()).map(|(a, b)| *a * b).fold(F128::zero(), |a, b| a + b);
 
         let mut prover = Prodcheck::new(p_polys.clone(), q_polys.clone(), claim, true, false);
+        if fused {
+            prover = prover.with_fused_bind();
+        }
 
         for i in 0..num_vars {
             let round_poly = prover.round_msg().coeffs(claim);
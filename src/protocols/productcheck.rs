@@ -0,0 +1,221 @@
+use num_traits::{One, Zero};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{field::F128, traits::{CompressedPoly, SumcheckObject}, utils::log2_exact};
+
+use super::utils::evaluate_univar;
+
+/// A single summand of ProductSumcheck: coeff * P_{factors[0]}(x) * ... * P_{factors[k-1]}(x).
+#[derive(Clone, Debug)]
+pub struct ProductTerm {
+    pub coeff: F128,
+    pub factors: Vec<usize>,
+}
+
+impl ProductTerm {
+    pub fn new(factors: Vec<usize>) -> Self {
+        Self { coeff: F128::one(), factors }
+    }
+
+    pub fn with_coeff(coeff: F128, factors: Vec<usize>) -> Self {
+        Self { coeff, factors }
+    }
+}
+
+/// Generalization of Prodcheck to sums of products of arbitrary arity, i.e. it proves
+/// sum_x sum_t coeff_t * prod_{j in terms[t]} P_j(x).
+/// Round polynomials have degree equal to the maximal arity of a term. Polynomials can appear in several terms,
+/// and several times in the same term.
+pub struct ProductSumcheck {
+    pub polys: Vec<Vec<F128>>,
+    pub terms: Vec<ProductTerm>,
+    pub claim: F128,
+    pub challenges: Vec<F128>,
+    degree: usize,
+
+    cached_round_msg: Option<CompressedPoly>,
+}
+
+pub struct ProductSumcheckOutput {
+    pub evals: Vec<F128>,
+}
+
+impl ProductSumcheck {
+    pub fn new(
+        polys: Vec<Vec<F128>>,
+        terms: Vec<ProductTerm>,
+        initial_claim: F128,
+        check_init_claim: bool,
+    ) -> Self {
+        assert!(polys.len() > 0);
+        let num_vars = log2_exact(polys[0].len());
+        for poly in polys.iter() {
+            assert!(poly.len() == 1 << num_vars);
+        }
+        for term in terms.iter() {
+            for &j in term.factors.iter() {
+                assert!(j < polys.len());
+            }
+        }
+        // Degree is at least 1, so that the round message always has linear coefficient to be compressed.
+        let degree = terms.iter().map(|t| t.factors.len()).max().unwrap_or(0).max(1);
+
+        if check_init_claim {
+            let expected_claim = (0 .. 1 << num_vars).map(|i| {
+                terms.iter().map(|t| {
+                    t.factors.iter().fold(t.coeff, |acc, &j| acc * polys[j][i])
+                }).fold(F128::zero(), |a, b| a + b)
+            }).fold(F128::zero(), |a, b| a + b);
+            assert!(initial_claim == expected_claim);
+        }
+
+        Self { polys, terms, claim: initial_claim, challenges: vec![], degree, cached_round_msg: None }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn finish(self) -> ProductSumcheckOutput {
+        let evals = self.polys.iter().map(|poly| {assert!(poly.len() == 1); poly[0]}).collect();
+        ProductSumcheckOutput { evals }
+    }
+
+    /// Adds coefficients of sum_t coeff_t * prod_j (P_j(2i) + t * (P_j(2i) + P_j(2i + 1))) to acc.
+    fn accumulate(&self, i: usize, acc: &mut [F128], scratch: &mut [F128]) {
+        for term in self.terms.iter() {
+            scratch[0] = term.coeff;
+            let mut deg = 0;
+            for &j in term.factors.iter() {
+                let a = self.polys[j][2 * i];
+                let b = self.polys[j][2 * i + 1] + a;
+                // multiply scratch[0..=deg] by (a + b t)
+                scratch[deg + 1] = scratch[deg] * b;
+                for k in (1..=deg).rev() {
+                    scratch[k] = scratch[k] * a + scratch[k - 1] * b;
+                }
+                scratch[0] *= a;
+                deg += 1;
+            }
+            for k in 0..=deg {
+                acc[k] += scratch[k];
+            }
+        }
+    }
+}
+
+impl SumcheckObject for ProductSumcheck {
+    fn is_reverse_order(&self) -> bool {
+        false
+    }
+
+    fn bind(&mut self, challenge: F128) {
+        assert!(self.polys[0].len() > 1, "The protocol has already ended.");
+        let half = self.polys[0].len() / 2;
+
+        let round_poly = self.round_msg().coeffs(self.claim);
+        self.claim = evaluate_univar(&round_poly, challenge);
+        self.challenges.push(challenge);
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            for poly in self.polys.iter_mut() {
+                for j in 0..half {
+                    poly[j] = poly[2 * j] + (poly[2 * j + 1] + poly[2 * j]) * challenge;
+                }
+                poly.truncate(half);
+            }
+        }
+
+        #[cfg(feature = "parallel")]
+        {
+            self.polys = self.polys.iter().map(|poly| {
+                (0..half).into_par_iter().map(|j| {
+                    poly[2 * j] + (poly[2 * j + 1] + poly[2 * j]) * challenge
+                }).collect()
+            }).collect();
+        }
+
+        self.cached_round_msg = None;
+    }
+
+    fn round_msg(&mut self) -> CompressedPoly {
+        assert!(self.polys[0].len() > 1, "The protocol has already ended.");
+        let half = self.polys[0].len() / 2;
+
+        if self.cached_round_msg.is_some() {
+            return self.cached_round_msg.as_ref().unwrap().clone()
+        }
+
+        let d = self.degree;
+        let zero = || (vec![F128::zero(); d + 1], vec![F128::zero(); d + 1]);
+
+        #[cfg(not(feature = "parallel"))]
+        let (response, _) = (0 .. half).fold(zero(), |(mut acc, mut scratch), i| {
+            self.accumulate(i, &mut acc, &mut scratch);
+            (acc, scratch)
+        });
+
+        #[cfg(feature = "parallel")]
+        let (response, _) = (0 .. half).into_par_iter().fold(zero, |(mut acc, mut scratch), i| {
+            self.accumulate(i, &mut acc, &mut scratch);
+            (acc, scratch)
+        }).reduce(zero, |(mut a, s), (b, _)| {
+            a.iter_mut().zip(b.iter()).map(|(a, b)| *a += b).count();
+            (a, s)
+        });
+
+        let (compressed_response, _) = CompressedPoly::compress(&response);
+
+        self.cached_round_msg = Some(compressed_response.clone());
+        compressed_response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::protocols::utils::evaluate;
+
+    use super::*;
+
+    #[test]
+    fn product_sumcheck_works() {
+        let rng = &mut OsRng;
+        let num_vars = 12;
+
+        let polys : Vec<Vec<F128>> = (0..4).map(|_| (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect()).collect();
+        let terms = vec![
+            ProductTerm::new(vec![0, 1, 2, 3]),
+            ProductTerm::with_coeff(F128::rand(rng), vec![1, 1, 2]),
+            ProductTerm::new(vec![3]),
+        ];
+
+        let mut claim = (0 .. 1 << num_vars).map(|i| {
+            terms.iter().map(|t| t.factors.iter().fold(t.coeff, |acc, &j| acc * polys[j][i])).fold(F128::zero(), |a, b| a + b)
+        }).fold(F128::zero(), |a, b| a + b);
+
+        let mut prover = ProductSumcheck::new(polys.clone(), terms.clone(), claim, true);
+        assert!(prover.degree() == 4);
+
+        for _ in 0..num_vars {
+            let round_poly = prover.round_msg().coeffs(claim);
+            assert!(round_poly.len() == 5);
+            let challenge = F128::rand(rng);
+            claim = evaluate_univar(&round_poly, challenge);
+            prover.bind(challenge);
+        }
+
+        let challenges = prover.challenges.clone();
+        let ProductSumcheckOutput { evals } = prover.finish();
+        for j in 0..4 {
+            assert!(evals[j] == evaluate(&polys[j], &challenges));
+        }
+
+        let expected_claim = terms.iter().map(|t| {
+            t.factors.iter().fold(t.coeff, |acc, &j| acc * evals[j])
+        }).fold(F128::zero(), |a, b| a + b);
+        assert!(expected_claim == claim);
+    }
+}
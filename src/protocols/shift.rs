@@ -0,0 +1,201 @@
+// Shift (rotation) check, reducing claims about cyclically shifted polynomials to claims about the polynomials
+// themselves. It is meant to wire outputs of one round into inputs of the next one; no prover uses it yet (the
+// Keccak rounds are wired directly, see examples::keccak::permutation), it is exposed as pipeline::ShiftStage.
+
+// shift_k(P) shifts P by k inside of every block of size 2^b (given by the first b variables):
+// shift_k(P)(x_lo, x_hi) = P(x_lo + k mod 2^b, x_hi).
+// Then shift_k(P)(r) = sum_y P(y) eq(r_hi, y_hi) S_k(r_lo, y_lo), where S_k(x, y) = [y = x + k mod 2^b] is the shift
+// indicator. Its multilinear extension is evaluated by the verifier in O(b) using the carry recursion (see
+// shift_indicator_ev), and the sum is proven by Prodcheck.
+
+use num_traits::{One, Zero};
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{field::F128, traits::{CompressedPoly, SumcheckObject}};
+
+use super::{prodcheck::{Prodcheck, ProdcheckOutput}, utils::{eq_ev, eq_poly, evaluate_univar}};
+
+/// Computes shift_k(P), i.e. ret[i] = poly[i'], where i' differs from i by adding k to the lower block_vars bits.
+pub fn shift(poly: &[F128], block_vars: usize, k: usize) -> Vec<F128> {
+    let block = 1 << block_vars;
+    assert!(poly.len().is_multiple_of(block));
+    let mask = block - 1;
+
+    #[cfg(not(feature = "parallel"))]
+    let ret = (0..poly.len()).map(|i| poly[(i & !mask) | ((i + k) & mask)]).collect();
+    #[cfg(feature = "parallel")]
+    let ret = (0..poly.len()).into_par_iter().map(|i| poly[(i & !mask) | ((i + k) & mask)]).collect();
+
+    ret
+}
+
+/// Evaluates the multilinear extension of [y = x + k mod 2^b] in the point (x, y), where b = x.len() = y.len().
+/// Bits of x and y are processed from the lowest one, keeping track of the weight of both values of the carry.
+pub fn shift_indicator_ev(k: usize, x: &[F128], y: &[F128]) -> F128 {
+    assert!(x.len() == y.len());
+    let b = x.len();
+    assert!(b == 0 || k < 1 << b);
+    let one = F128::one();
+
+    // weights[c] is the extension of the indicator of the lower i bits, restricted to carry c.
+    let mut weights = [one, F128::zero()];
+    for i in 0..b {
+        let k_i = (k >> i) & 1;
+        let ex = [one + x[i], x[i]];
+        let ey = [one + y[i], y[i]];
+        let mut next = [F128::zero(); 2];
+        for (c, w) in weights.iter().enumerate() {
+            for (x_i, e) in ex.iter().enumerate() {
+                let s = x_i ^ k_i ^ c;
+                let carry = (x_i + k_i + c) >> 1;
+                next[carry] += *w * e * ey[s];
+            }
+        }
+        weights = next;
+    }
+    weights[0] + weights[1]
+}
+
+/// Proves claims shift_k(P_i)(pt) = claims[i].
+pub struct ShiftCheck<const N: usize> {
+    polys: [Vec<F128>; N],
+    pt: Vec<F128>,
+    block_vars: usize,
+    k: usize,
+    initial_claims: [F128; N],
+}
+
+impl<const N: usize> ShiftCheck<N> {
+    pub fn new(polys: [Vec<F128>; N], pt: Vec<F128>, block_vars: usize, k: usize, initial_claims: [F128; N]) -> Self {
+        assert!(block_vars <= pt.len());
+        assert!(k < 1 << block_vars);
+        for poly in polys.iter() {
+            assert!(poly.len() == 1 << pt.len());
+        }
+        Self { polys, pt, block_vars, k, initial_claims }
+    }
+
+    pub fn folding_challenge(self, gamma: F128) -> ShiftCheckSingle {
+        let Self { polys, pt, block_vars, k, initial_claims } = self;
+        let block = 1 << block_vars;
+
+        // q(y) = eq(pt, y - k), i.e. eq is shifted by -k inside of every block.
+        let eq = eq_poly(&pt);
+        let q = shift(&eq, block_vars, block - k);
+
+        let mut q_polys = vec![];
+        let mut gamma_pow = F128::one();
+        for _ in 0..N {
+            #[cfg(not(feature = "parallel"))]
+            q_polys.push(q.iter().map(|x| *x * gamma_pow).collect());
+            #[cfg(feature = "parallel")]
+            q_polys.push((0..q.len()).into_par_iter().map(|i| q[i] * gamma_pow).collect());
+            gamma_pow *= gamma;
+        }
+
+        let claim = evaluate_univar(&initial_claims, gamma);
+        ShiftCheckSingle {
+            object: Prodcheck::new(polys.to_vec(), q_polys, claim, false, false).with_fused_bind(),
+        }
+    }
+}
+
+pub struct ShiftCheckSingle {
+    pub object: Prodcheck,
+}
+
+impl ShiftCheckSingle {
+    /// Returns evaluations of P_i in the point given by the challenges.
+    pub fn finish(self) -> Vec<F128> {
+        let ProdcheckOutput { p_evs, .. } = self.object.finish();
+        p_evs
+    }
+}
+
+impl SumcheckObject for ShiftCheckSingle {
+    fn is_reverse_order(&self) -> bool {
+        self.object.is_reverse_order()
+    }
+
+    fn bind(&mut self, challenge: F128) {
+        self.object.bind(challenge)
+    }
+
+    fn round_msg(&mut self) -> CompressedPoly {
+        self.object.round_msg()
+    }
+}
+
+/// Verifier side: computes the expected final claim of ShiftCheck from the evaluations returned by the prover.
+pub fn shift_final_claim(pt: &[F128], block_vars: usize, k: usize, rs: &[F128], gamma: F128, evals: &[F128]) -> F128 {
+    let b = block_vars;
+    let q_ev = eq_ev(&pt[b..], &rs[b..]) * shift_indicator_ev(k, &pt[..b], &rs[..b]);
+    evaluate_univar(evals, gamma) * q_ev
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use rand::rngs::OsRng;
+
+    use crate::protocols::{pipeline::{run_sumcheck, EvalClaim, ShiftStage, Stage}, utils::evaluate};
+
+    use super::*;
+
+    #[test]
+    fn shift_indicator_is_correct() {
+        let rng = &mut OsRng;
+        let b = 5;
+        let x : Vec<_> = repeat_with(|| F128::rand(rng)).take(b).collect();
+        let y : Vec<_> = repeat_with(|| F128::rand(rng)).take(b).collect();
+        let eq_x = eq_poly(&x);
+        let eq_y = eq_poly(&y);
+        for k in [0, 1, 7, 31] {
+            let naive = (0 .. 1 << b).map(|i| eq_x[i] * eq_y[(i + k) % (1 << b)]).fold(F128::zero(), |a, b| a + b);
+            assert!(shift_indicator_ev(k, &x, &y) == naive);
+        }
+    }
+
+    #[test]
+    fn shift_check_works() {
+        let rng = &mut OsRng;
+        let num_vars = 12;
+        let block_vars = 6;
+        let k = 13;
+
+        let polys : [Vec<F128>; 3] = std::array::from_fn(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect());
+        let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let claims : [F128; 3] = std::array::from_fn(|i| evaluate(&shift(&polys[i], block_vars, k), &pt));
+
+        let gamma = F128::rand(rng);
+        let mut prover = ShiftCheck::new(polys.clone(), pt.clone(), block_vars, k, claims).folding_challenge(gamma);
+        let (final_claim, rs) = run_sumcheck(&mut prover, evaluate_univar(&claims, gamma), num_vars, 2, rng);
+        let evals = prover.finish();
+
+        for (poly, ev) in polys.iter().zip(evals.iter()) {
+            assert!(evaluate(poly, &rs) == *ev);
+        }
+        assert!(shift_final_claim(&pt, block_vars, k, &rs, gamma, &evals) == final_claim);
+    }
+
+    #[test]
+    fn shift_stage_wires_rounds() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+        let block_vars = 4;
+
+        // Output of the previous round, and the input of the next one, which is its rotation by 3.
+        let output : [Vec<F128>; 2] = std::array::from_fn(|_| repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect());
+        let input : Vec<Vec<F128>> = output.iter().map(|poly| shift(poly, block_vars, 3)).collect();
+
+        let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let values = input.iter().map(|poly| evaluate(poly, &pt)).collect();
+        let claim = ShiftStage::new(output.clone(), block_vars, 3).run(EvalClaim { point: pt, values }, rng);
+
+        for (poly, value) in output.iter().zip(claim.values.iter()) {
+            assert!(evaluate(poly, &claim.point) == *value);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// Subfield membership check: proves that every value v of a polynomial P on the boolean hypercube satisfies
+// v^(2^k) = v, i.e. lies in the subfield GF(2^k) (for k dividing 128; for k = 1 this is booleanity).
+
+// The map L(v) = Fr^k(v) + v is F2-linear, so on the hypercube L(P(x)) = sum_i L(b_i) P_i(x), where P_i are
+// the (boolean) coordinate polynomials of P. The extension of L(P) vanishes iff all its values vanish, so it is enough
+// to check that sum_i L(b_i) P_i(r) = 0 in a random point r. Coordinate evaluations P_i(r) are given by evaluations of
+// P in the inverse Frobenius orbit of r (see untwist_evals), which is exactly the claim consumed by MulticlaimCheck.
+// L(b_i) are read from the precomputed Frobenius table.
+
+use num_traits::Zero;
+
+use crate::{field::F128, precompute::frobenius_table::FROBENIUS};
+
+use super::{pipeline::OrbitClaim, utils::{coordinate_evals, twist_evals, untwist_evals}};
+
+pub struct SubfieldCheck {
+    coeffs: Vec<F128>, // L(b_i)
+}
+
+impl SubfieldCheck {
+    pub fn new(k: usize) -> Self {
+        assert!(k > 0 && k < 128);
+        let coeffs = (0..128).map(|i| F128::from_raw(FROBENIUS[k][i]) + F128::basis(i)).collect();
+        Self { coeffs }
+    }
+
+    /// Computes L(v) = v^(2^k) + v.
+    pub fn apply(&self, v: F128) -> F128 {
+        (0..128).filter(|&i| (v.raw() >> i) & 1 == 1).fold(F128::zero(), |acc, i| acc + self.coeffs[i])
+    }
+
+    /// Prover side: returns evaluations of polys in the inverse Frobenius orbit of pt (in the twisted form, as in
+    /// BoolCheck output).
+    pub fn prove(&self, polys: &[Vec<F128>], pt: &[F128]) -> OrbitClaim {
+        let mut frob_evals = vec![];
+        for poly in polys {
+            let mut evals = coordinate_evals(poly, pt);
+            twist_evals(&mut evals);
+            frob_evals.extend(evals);
+        }
+        OrbitClaim { point: pt.to_vec(), frob_evals }
+    }
+
+    /// Verifier side: checks that the extension of L(P) vanishes in the point of the claim, for every polynomial.
+    /// The claim itself must then be reduced (using MulticlaimCheck) and checked against the commitment.
+    pub fn verify(&self, claim: &OrbitClaim) -> bool {
+        claim.frob_evals.len().is_multiple_of(128) && claim.frob_evals.chunks(128).all(|chunk| {
+            let mut coords = chunk.to_vec();
+            untwist_evals(&mut coords);
+            coords.iter().zip(self.coeffs.iter()).fold(F128::zero(), |acc, (c, l)| acc + *c * l) == F128::zero()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use num_traits::One;
+    use rand::{rngs::OsRng, Rng};
+
+    use crate::protocols::{pipeline::{MulticlaimStage, Stage}, utils::evaluate};
+
+    use super::*;
+
+    /// Relative trace from F128 to GF(2^k), maps onto the subfield.
+    fn trace(v: F128, k: usize) -> F128 {
+        (0 .. 128 / k).fold(F128::zero(), |acc, j| acc + v.frob((j * k) as i32))
+    }
+
+    #[test]
+    fn subfield_check_works() {
+        let rng = &mut OsRng;
+        let num_vars = 8;
+        let k = 8;
+        let check = SubfieldCheck::new(k);
+
+        let polys : [Vec<F128>; 2] = std::array::from_fn(|_| {
+            repeat_with(|| trace(F128::rand(rng), k)).take(1 << num_vars).collect()
+        });
+        for v in polys[0].iter() {
+            assert!(v.frob(k as i32) == *v);
+            assert!(check.apply(*v) == F128::zero());
+        }
+
+        let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+        let claim = check.prove(&polys, &pt);
+        assert!(check.verify(&claim));
+
+        // The orbit claim is consumed by MulticlaimCheck.
+        let claim = MulticlaimStage::new(&polys).run(claim, rng);
+        for (poly, value) in polys.iter().zip(claim.values.iter()) {
+            assert!(evaluate(poly, &claim.point) == *value);
+        }
+
+        let mut bad_polys = polys.clone();
+        bad_polys[1][17] = F128::rand(rng);
+        let claim = check.prove(&bad_polys, &pt);
+        assert!(!check.verify(&claim));
+    }
+
+    #[test]
+    fn booleanity_check_works() {
+        let rng = &mut OsRng;
+        let num_vars = 6;
+        let check = SubfieldCheck::new(1);
+        let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars).collect();
+
+        let mut poly : Vec<_> = (0 .. 1 << num_vars).map(|_| if rng.gen_bool(0.5) { F128::one() } else { F128::zero() }).collect();
+        assert!(check.verify(&check.prove(&[poly.clone()], &pt)));
+
+        poly[5] = F128::basis(1);
+        assert!(!check.verify(&check.prove(&[poly], &pt)));
+    }
+}
@@ -46,6 +46,33 @@ pub fn untwist_evals(twisted_evals: &mut [F128]) {
     twisted_evals.copy_from_slice(&untwisted);
 }
 
+/// Computes P_i(pt) for all coordinate polynomials of P.
+pub fn coordinate_evals(poly: &[F128], pt: &[F128]) -> Vec<F128> {
+    assert!(poly.len() == 1 << pt.len());
+    let eq = eq_poly(pt);
+
+    let add_row = |mut acc: Vec<F128>, (x, e): (&F128, &F128)| {
+        for (i, a) in acc.iter_mut().enumerate() {
+            if u128_idx(&x.raw(), i) {
+                *a += *e;
+            }
+        }
+        acc
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    let ret = poly.iter().zip(eq.iter()).fold(vec![F128::zero(); 128], add_row);
+    #[cfg(feature = "parallel")]
+    let ret = poly.par_iter().zip(eq.par_iter())
+        .fold(|| vec![F128::zero(); 128], add_row)
+        .reduce(|| vec![F128::zero(); 128], |mut a, b| {
+            a.iter_mut().zip(b.iter()).map(|(x, y)| *x += *y).count();
+            a
+        });
+
+    ret
+}
+
 pub fn eq_poly_legacy(pt: &[F128]) -> Vec<F128> {
     let l = pt.len();
     let mut ret = Vec::with_capacity(1 << l);
@@ -141,6 +168,34 @@ pub fn evaluate_univar(poly: &[F128], at: F128) -> F128 {
     ret
 }
 
+/// Returns coefficients of the unique polynomial of degree < xs.len() taking values ys in distinct points xs.
+pub fn interpolate_univar(xs: &[F128], ys: &[F128]) -> Vec<F128> {
+    assert!(xs.len() == ys.len());
+    let l = xs.len();
+    let mut ret = vec![F128::zero(); l];
+    for i in 0..l {
+        // Lagrange basis polynomial prod_{j != i} (x - xs[j]) / (xs[i] - xs[j]).
+        let mut basis = vec![F128::zero(); l];
+        basis[0] = F128::one();
+        let mut denom = F128::one();
+        let mut deg = 0;
+        for j in 0..l {
+            if j == i {continue};
+            denom *= xs[i] + xs[j];
+            deg += 1;
+            for k in (1..=deg).rev() {
+                basis[k] = basis[k - 1] + basis[k] * xs[j];
+            }
+            basis[0] *= xs[j];
+        }
+        let multiplier = ys[i] * denom.inv();
+        for k in 0..l {
+            ret[k] += basis[k] * multiplier;
+        }
+    }
+    ret
+}
+
 pub fn bits_to_trits(mut x: usize) -> usize {
     let mut multiplier = 1;
     let mut ret = 0;
@@ -443,6 +498,23 @@ pub fn drop_top_bit(x: usize) -> (usize, usize) {
     (x - (1 << s), s)
 }
 
+/// For every consecutive group of 8 values of eq, computes all 256 subset sums. Subset is encoded by a byte,
+/// with i-th bit responsible for i-th element of a group.
+pub fn eq_byte_sums(eq: &[F128]) -> Vec<F128> {
+    assert!(eq.len() % 8 == 0);
+    let mut eq_sums = Vec::with_capacity(256 * eq.len() / 8);
+
+    for i in 0..eq.len()/8 {
+        eq_sums.push(F128::zero());
+        for j in 1..256 {
+            let (sum_idx, eq_idx) = drop_top_bit(j);
+            let tmp = eq[i * 8 + eq_idx] + eq_sums[i * 256 + sum_idx];
+            eq_sums.push(tmp);
+        }
+    }
+    eq_sums
+}
+
 //#[unroll::unroll_for_loops]
 /// A new version of restrict, to work with boolcheck's contigious array API
 /// It returns restrictions of all coordinates of all polynomials, and writes them in a single contigious array.
@@ -460,16 +532,7 @@ pub fn restrict(polys: &[&[F128]], coords: &[F128], dims: usize) -> Vec<F128> {
 
     assert!(eq.len() % 16 == 0, "Technical condition for now.");
 
-    let mut eq_sums = Vec::with_capacity(256 * eq.len() / 8);
-
-    for i in 0..eq.len()/8 {
-        eq_sums.push(F128::zero());
-        for j in 1..256 {
-            let (sum_idx, eq_idx) = drop_top_bit(j);
-            let tmp = eq[i * 8 + eq_idx] + eq_sums[i * 256 + sum_idx];
-            eq_sums.push(tmp);
-        }
-    }
+    let eq_sums = eq_byte_sums(&eq);
 
     let mut ret = vec![F128::zero(); num_chunks * 128 * n];
     let ret_ptr = ret.as_shared_mut_ptr();
@@ -528,16 +591,7 @@ pub fn restrict_legacy(poly: &[F128], coords: &[F128], dims: usize) -> Vec<Vec<F
 
     assert!(eq.len() % 16 == 0, "Technical condition for now.");
 
-    let mut eq_sums = Vec::with_capacity(256 * eq.len() / 8);
-
-    for i in 0..eq.len()/8 {
-        eq_sums.push(F128::zero());
-        for j in 1..256 {
-            let (sum_idx, eq_idx) = drop_top_bit(j);
-            let tmp = eq[i * 8 + eq_idx] + eq_sums[i * 256 + sum_idx];
-            eq_sums.push(tmp);
-        }
-    }
+    let eq_sums = eq_byte_sums(&eq);
 
     let mut ret = vec![vec![F128::zero(); num_chunks]; 128];
     let ret_ptrs : [_; 128] = ret.iter_mut().map(|v| v.as_shared_mut_ptr())
@@ -586,6 +640,76 @@ pub fn restrict_legacy(poly: &[F128], coords: &[F128], dims: usize) -> Vec<Vec<F
     ret
 }
 
+/// Restricts the last coords.len() variables of a polynomial, i.e. computes P(x_0, ..., x_{k-1}, coords).
+/// Uses the same bit-slicing as restrict: every F128 is treated as 128 boolean coordinates, and each coordinate
+/// is restricted using precomputed subset sums of eq, so no multiplications are done in the main cycle.
+/// Coordinates are then packed back.
+/// Work is split into blocks of consecutive outputs, so that a single pass over a cache line of input serves
+/// all outputs of a block.
+pub fn restrict_high(poly: &[F128], coords: &[F128]) -> Vec<F128> {
+    let dims = log2_exact(poly.len());
+    assert!(coords.len() <= dims);
+    let stride = 1 << (dims - coords.len());
+
+    let eq = eq_poly(coords);
+    // The main cycle consumes eq in steps of 16, fewer than 4 coordinates are restricted directly.
+    if eq.len() < 16 {
+        #[cfg(not(feature = "parallel"))]
+        let iter = 0..stride;
+        #[cfg(feature = "parallel")]
+        let iter = (0..stride).into_par_iter();
+
+        return iter.map(|i| {
+            eq.iter().enumerate().fold(F128::zero(), |acc, (j, e)| acc + *e * poly[i + j * stride])
+        }).collect();
+    }
+    let eq_sums = eq_byte_sums(&eq);
+
+    let block_size = std::cmp::min(16, stride);
+
+    let mut ret = vec![F128::zero(); stride];
+
+    #[cfg(not(feature = "parallel"))]
+    let iter = ret.chunks_mut(block_size);
+    #[cfg(feature = "parallel")]
+    let iter = ret.par_chunks_mut(block_size);
+
+    iter.enumerate().map(|(block_idx, out)| {
+        let mut acc = vec![[F128::zero(); 128]; out.len()];
+        for j in 0 .. eq.len() / 16 { // Step by 16
+            let v0 = &eq_sums[j * 512 .. j * 512 + 256];
+            let v1 = &eq_sums[j * 512 + 256 .. j * 512 + 512];
+
+            for (t, acc) in acc.iter_mut().enumerate() {
+                let start = block_idx * block_size + t + 16 * j * stride;
+                let bytearr : [[u8; 16]; 16] = std::array::from_fn(|e| cast(poly[start + e * stride]));
+
+                // Iteration over bytes
+                for s in 0..16 {
+                    let mut w = [
+                        bytearr[0][s], bytearr[1][s], bytearr[2][s], bytearr[3][s],
+                        bytearr[4][s], bytearr[5][s], bytearr[6][s], bytearr[7][s],
+                        bytearr[8][s], bytearr[9][s], bytearr[10][s], bytearr[11][s],
+                        bytearr[12][s], bytearr[13][s], bytearr[14][s], bytearr[15][s],
+                    ];
+
+                    for u in 0..8 {
+                        let bits = v_movemask_epi8(w) as u16;
+                        acc[s * 8 + 7 - u] += v0[(bits & 255) as usize] + v1[((bits >> 8) & 255) as usize];
+                        w = v_slli_epi64::<1>(w);
+                    }
+                }
+            }
+        }
+
+        for (o, acc) in out.iter_mut().zip(acc.iter()) {
+            *o = (0..128).map(|k| F128::basis(k) * acc[k]).fold(F128::zero(), |a, b| a + b);
+        }
+    }).count();
+
+    ret
+}
+
 /// This implements efficient matrices using method of 4 Russians, 128x128.
 /// Technically we could implements 128 x N, and use in restrict, but I will avoid it for now. 
 #[derive(Clone, Debug)]
@@ -731,6 +855,34 @@ mod tests {
         assert!(old_answer.into_iter().map(|x|x.into_iter().flatten()).flatten().collect::<Vec<_>>() == new_answer);
     }
 
+    #[test]
+    fn restrict_high_as_expected() {
+        let rng = &mut OsRng;
+        let num_vars = 10;
+        let poly : Vec<_> = repeat_with(|| F128::rand(rng)).take(1 << num_vars).collect();
+
+        // Fewer than 4 restricted variables go through the direct path.
+        for num_vars_to_restrict in [0, 1, 2, 3, 4, 6] {
+            let pt : Vec<_> = repeat_with(|| F128::rand(rng)).take(num_vars_to_restrict).collect();
+            let eq = eq_poly(&pt);
+            let stride = 1 << (num_vars - num_vars_to_restrict);
+            let expected : Vec<_> = (0..stride).map(|i| {
+                (0..eq.len()).map(|j| eq[j] * poly[i + j * stride]).fold(F128::zero(), |a, b| a + b)
+            }).collect();
+
+            assert!(restrict_high(&poly, &pt) == expected);
+        }
+    }
+
+    #[test]
+    fn interpolate_univar_as_expected() {
+        let rng = &mut OsRng;
+        let poly : Vec<_> = (0..6).map(|_| F128::rand(rng)).collect();
+        let xs : Vec<_> = (0..6).map(|_| F128::rand(rng)).collect();
+        let ys : Vec<_> = xs.iter().map(|x| evaluate_univar(&poly, *x)).collect();
+        assert_eq!(interpolate_univar(&xs, &ys), poly);
+    }
+
     #[test]
     fn twist_untwist() {
         let rng = &mut OsRng;
@@ -0,0 +1,429 @@
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use crate::{
+    commitment::{CommitmentBytes, PolynomialCommitment},
+    field::F128,
+    traits::{CompressedPoly, SumcheckObject},
+    transcript::{prove_sumcheck, verify_sumcheck, Transcript},
+};
+
+use super::utils::evaluate_univar;
+
+/// Random masking polynomial g(x) = sum_i g_i(x_i) * prod_{k != i} x_k, where g_i are random univariate polynomials
+/// of a given degree.
+/// Usual mask sum_i g_i(x_i) does not work in characteristic 2 - its sum over any subcube of dimension >= 1 vanishes.
+/// Multiplication by prod_{k != i} x_k fixes this: the sum over the hypercube is sum_i g_i(0) + g_i(1), and the round
+/// polynomial of the mask in round j contains prod_{k < j} r_k * g_j(t), hiding all coefficients of the round message.
+/// Coefficients of g_i are committed by the prover (see committed_poly), and g(r) is opened in the end.
+#[derive(Clone, Debug)]
+pub struct ZkMask {
+    pub coeffs: Vec<Vec<F128>>, // coeffs[i] are coefficients of g_i.
+}
+
+impl ZkMask {
+    pub fn new(coeffs: Vec<Vec<F128>>) -> Self {
+        assert!(!coeffs.is_empty());
+        // Degree 0 would not hide anything, and round polynomials of the mask access the linear coefficient.
+        assert!(coeffs.iter().all(|g| g.len() >= 2), "Mask polynomials must have degree at least 1.");
+        Self { coeffs }
+    }
+
+    pub fn rand<RNG: Rng>(rng: &mut RNG, num_vars: usize, degree: usize) -> Self {
+        Self::new((0..num_vars).map(|_| (0..degree + 1).map(|_| F128::rand(rng)).collect()).collect())
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.coeffs.len()
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coeffs.iter().map(|g| g.len() - 1).max().unwrap()
+    }
+
+    /// Sum of g_i(0) + g_i(1) = sum_x g(x).
+    pub fn sum(&self) -> F128 {
+        self.coeffs.iter().map(|g| g[1..].iter().fold(F128::zero(), |a, b| a + b)).fold(F128::zero(), |a, b| a + b)
+    }
+
+    pub fn evaluate(&self, pt: &[F128]) -> F128 {
+        assert!(pt.len() == self.num_vars());
+        let row_evals : Vec<_> = self.coeffs.iter().zip(pt.iter()).map(|(g, x)| evaluate_univar(g, *x)).collect();
+        mask_eval_from_rows(&row_evals, pt)
+    }
+
+    /// Number of variables of the committed polynomial of a mask with num_vars variables and given degree.
+    pub fn committed_vars(num_vars: usize, degree: usize) -> usize {
+        coeff_vars(degree) + ceil_log2(num_vars)
+    }
+
+    /// Multilinear polynomial committing to the mask. Its restriction to x_hi = i (the last ceil_log2(num_vars)
+    /// variables) is the polynomial Q_i(y) = sum_s c_{i, s} prod_k y_k^{s_k}, s_k being the bits of s, so that
+    /// Q_i(r, r^2, r^4, ...) = g_i(r). Values of Q_i on the hypercube are subset sums of the coefficients of g_i.
+    pub fn committed_poly(&self) -> Vec<F128> {
+        let a = coeff_vars(self.degree());
+        let mut ret = vec![F128::zero(); 1 << Self::committed_vars(self.num_vars(), self.degree())];
+        for (i, g) in self.coeffs.iter().enumerate() {
+            let chunk = &mut ret[i << a .. (i + 1) << a];
+            chunk[..g.len()].copy_from_slice(g);
+            for k in 0..a {
+                for x in 0..1 << a {
+                    if (x >> k) & 1 == 1 {
+                        let v = chunk[x ^ (1 << k)];
+                        chunk[x] += v;
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+fn coeff_vars(degree: usize) -> usize {
+    ceil_log2(degree + 1)
+}
+
+fn ceil_log2(x: usize) -> usize {
+    x.next_power_of_two().trailing_zeros() as usize
+}
+
+/// Point in which the committed polynomial of the mask evaluates to g_i(r).
+pub fn mask_opening_point(i: usize, r: F128, num_vars: usize, degree: usize) -> Vec<F128> {
+    let mut pt = Vec::with_capacity(ZkMask::committed_vars(num_vars, degree));
+    let mut power = r;
+    for _ in 0..coeff_vars(degree) {
+        pt.push(power);
+        power *= power;
+    }
+    pt.extend((0..ceil_log2(num_vars)).map(|k| F128::new((i >> k) & 1 == 1)));
+    pt
+}
+
+/// Computes g(pt) = sum_i g_i(pt_i) * prod_{k != i} pt_k from row_evals[i] = g_i(pt_i).
+pub fn mask_eval_from_rows(row_evals: &[F128], pt: &[F128]) -> F128 {
+    assert!(row_evals.len() == pt.len());
+    let n = pt.len();
+    let mut suffix_products = vec![F128::one(); n + 1];
+    for i in (0..n).rev() {
+        suffix_products[i] = suffix_products[i + 1] * pt[i];
+    }
+    let mut ret = F128::zero();
+    let mut prefix_product = F128::one();
+    for i in 0..n {
+        ret += row_evals[i] * prefix_product * suffix_products[i + 1];
+        prefix_product *= pt[i];
+    }
+    ret
+}
+
+/// Optional zero-knowledge mode for any sumcheck object. Prover samples a mask (ZkMask), commits to it and sends
+/// its sum; after receiving the challenge rho, both parties run sumcheck for claim + rho * mask_sum.
+/// In the end, the verifier obtains the claim of the wrapped object by unmask_final_claim, using opening of the mask
+/// in the challenge point. Degree of the mask is configured per protocol, and should be at least the degree of round
+/// polynomials of the wrapped object.
+/// Note that this only hides round messages - final evaluation claims of the wrapped object must be handled by a
+/// hiding commitment.
+/// The number of rounds is the number of variables of the mask. For the non-interactive version, which commits to
+/// the mask and draws rho from the transcript, see prove_sumcheck_zk and verify_sumcheck_zk.
+pub struct ZkSumcheck<S: SumcheckObject> {
+    inner: S,
+    mask: ZkMask,
+    rho: F128,
+    pub claim: F128,
+    inner_claim: F128,
+    pub challenges: Vec<F128>,
+    prefix_product: F128, // prod_{k < j} r_k
+    prefix_sum: F128, // sum_{i < j} g_i(r_i) prod_{k < j, k != i} r_k
+    tail_sums: Vec<F128>, // tail_sums[j] = sum_{i >= j} g_i(0) + g_i(1)
+
+    cached_round_msg: Option<CompressedPoly>,
+}
+
+impl<S: SumcheckObject> ZkSumcheck<S> {
+    pub fn new(inner: S, inner_claim: F128, mask: ZkMask, rho: F128) -> Self {
+        assert!(!inner.is_reverse_order(), "Unsupported order.");
+        let n = mask.num_vars();
+        let mut tail_sums = vec![F128::zero(); n + 1];
+        for i in (0..n).rev() {
+            tail_sums[i] = tail_sums[i + 1] + mask.coeffs[i][1..].iter().fold(F128::zero(), |a, b| a + b);
+        }
+        let claim = masked_initial_claim(inner_claim, mask.sum(), rho);
+        Self {
+            inner,
+            mask,
+            rho,
+            claim,
+            inner_claim,
+            challenges: vec![],
+            prefix_product: F128::one(),
+            prefix_sum: F128::zero(),
+            tail_sums,
+            cached_round_msg: None,
+        }
+    }
+
+    pub fn curr_round(&self) -> usize {
+        self.challenges.len()
+    }
+
+    /// Returns the wrapped object (to be finished by the caller), and the evaluation of the mask in the challenge point.
+    pub fn finish(self) -> (S, F128) {
+        assert!(self.curr_round() == self.mask.num_vars(), "Protocol has not finished yet.");
+        let mask_eval = self.prefix_sum;
+        (self.inner, mask_eval)
+    }
+
+    /// Round polynomial of the mask: prefix_sum * t + prefix_product * (g_j(t) + tail_sums[j + 1] * t).
+    fn mask_round_poly(&self) -> Vec<F128> {
+        let j = self.curr_round();
+        let mut poly : Vec<_> = self.mask.coeffs[j].iter().map(|c| *c * self.prefix_product).collect();
+        poly[1] += self.prefix_sum + self.prefix_product * self.tail_sums[j + 1];
+        poly
+    }
+}
+
+impl<S: SumcheckObject> SumcheckObject for ZkSumcheck<S> {
+    fn is_reverse_order(&self) -> bool {
+        false
+    }
+
+    fn bind(&mut self, challenge: F128) {
+        let j = self.curr_round();
+        assert!(j < self.mask.num_vars(), "Protocol has already finished.");
+        let round_poly = self.round_msg().coeffs(self.claim);
+        self.claim = evaluate_univar(&round_poly, challenge);
+
+        let inner_poly = self.inner.round_msg().coeffs(self.inner_claim);
+        self.inner_claim = evaluate_univar(&inner_poly, challenge);
+        self.inner.bind(challenge);
+
+        self.prefix_sum = self.prefix_sum * challenge + evaluate_univar(&self.mask.coeffs[j], challenge) * self.prefix_product;
+        self.prefix_product *= challenge;
+        self.challenges.push(challenge);
+        self.cached_round_msg = None;
+    }
+
+    fn round_msg(&mut self) -> CompressedPoly {
+        assert!(self.curr_round() < self.mask.num_vars(), "Protocol has already finished.");
+        if let Some(msg) = &self.cached_round_msg {
+            return msg.clone()
+        }
+
+        let mut poly = self.inner.round_msg().coeffs(self.inner_claim);
+        let mask_poly = self.mask_round_poly();
+        assert!(mask_poly.len() >= poly.len(), "Degree of the mask is less than the degree of the wrapped object.");
+        poly.resize(mask_poly.len(), F128::zero());
+        for (s, c) in mask_poly.iter().enumerate() {
+            poly[s] += self.rho * c;
+        }
+
+        let (ret, expected_claim) = CompressedPoly::compress(&poly);
+        assert!(expected_claim == self.claim, "Current round: {}", self.curr_round()); // sanity check
+
+        self.cached_round_msg = Some(ret.clone());
+        ret
+    }
+}
+
+/// Verifier side: initial claim of the masked sumcheck.
+pub fn masked_initial_claim(claim: F128, mask_sum: F128, rho: F128) -> F128 {
+    claim + rho * mask_sum
+}
+
+/// Verifier side: recovers final claim of the wrapped object from the final claim of the masked sumcheck and the
+/// opening of the mask.
+pub fn unmask_final_claim(claim: F128, mask_eval: F128, rho: F128) -> F128 {
+    claim + rho * mask_eval
+}
+
+#[derive(Clone, Debug)]
+pub struct ZkSumcheckProof<C, Pr> {
+    pub mask_commitment: C,
+    pub mask_sum: F128,
+    pub round_msgs: Vec<CompressedPoly>,
+    /// row_evals[i] = g_i(r_i), opened from the mask commitment by row_openings[i].
+    pub row_evals: Vec<F128>,
+    pub row_openings: Vec<Pr>,
+}
+
+/// Prover side of non-interactive zero-knowledge sumcheck. Samples a mask of a given degree (which must be at least
+/// the degree of round polynomials of the object), commits to it with pcs, absorbs the commitment and the sum of
+/// the mask, draws rho, runs the masked sumcheck and opens the mask in the challenge point.
+/// Returns the proof, the wrapped object (to be finished by the caller) and the challenges.
+pub fn prove_sumcheck_zk<S: SumcheckObject, P: PolynomialCommitment, RNG: Rng>(
+    object: S,
+    claim: F128,
+    num_rounds: usize,
+    degree: usize,
+    pcs: &P,
+    rng: &mut RNG,
+    transcript: &mut Transcript,
+) -> (ZkSumcheckProof<P::Commitment, P::Proof>, S, Vec<F128>) {
+    assert!(pcs.num_vars() == ZkMask::committed_vars(num_rounds, degree));
+    let mask = ZkMask::rand(rng, num_rounds, degree);
+    let mask_sum = mask.sum();
+    let (mask_commitment, data) = pcs.commit(&mask.committed_poly());
+    transcript.absorb_bytes(&mask_commitment.to_bytes());
+    transcript.absorb(&[mask_sum]);
+    let rho = transcript.challenge();
+
+    let mut prover = ZkSumcheck::new(object, claim, mask.clone(), rho);
+    let (round_msgs, rs) = prove_sumcheck(&mut prover, num_rounds, transcript);
+    let (inner, _) = prover.finish();
+
+    let row_evals = mask.coeffs.iter().zip(rs.iter()).map(|(g, r)| evaluate_univar(g, *r)).collect();
+    let row_openings = rs.iter().enumerate()
+        .map(|(i, r)| pcs.open(&data, &mask_opening_point(i, *r, num_rounds, degree)))
+        .collect();
+
+    (ZkSumcheckProof { mask_commitment, mask_sum, round_msgs, row_evals, row_openings }, inner, rs)
+}
+
+/// Verifier side of non-interactive zero-knowledge sumcheck. Returns the final claim of the wrapped object and the
+/// challenges, or None if some round message has wrong degree or some opening of the mask is rejected.
+pub fn verify_sumcheck_zk<P: PolynomialCommitment>(
+    claim: F128,
+    proof: &ZkSumcheckProof<P::Commitment, P::Proof>,
+    degree: usize,
+    pcs: &P,
+    transcript: &mut Transcript,
+) -> Option<(F128, Vec<F128>)> {
+    let num_rounds = proof.round_msgs.len();
+    assert!(pcs.num_vars() == ZkMask::committed_vars(num_rounds, degree));
+    if proof.row_evals.len() != num_rounds || proof.row_openings.len() != num_rounds {
+        return None;
+    }
+    transcript.absorb_bytes(&proof.mask_commitment.to_bytes());
+    transcript.absorb(&[proof.mask_sum]);
+    let rho = transcript.challenge();
+
+    let (final_claim, rs) = verify_sumcheck(masked_initial_claim(claim, proof.mask_sum, rho), &proof.round_msgs, degree, transcript)?;
+    for (i, r) in rs.iter().enumerate() {
+        let point = mask_opening_point(i, *r, num_rounds, degree);
+        if !pcs.verify(&proof.mask_commitment, &point, proof.row_evals[i], &proof.row_openings[i]) {
+            return None;
+        }
+    }
+    let mask_eval = mask_eval_from_rows(&proof.row_evals, &rs);
+    Some((unmask_final_claim(final_claim, mask_eval, rho), rs))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{commitment::ligero::{Ligero, LigeroCommitment, LigeroProof}, protocols::{prodcheck::{Prodcheck, ProdcheckOutput}, utils::evaluate}};
+
+    use super::*;
+
+    #[test]
+    fn zk_prodcheck_works() {
+        let rng = &mut OsRng;
+        let num_vars = 8;
+
+        let p : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let q : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let initial_claim = p.iter().zip(q.iter()).fold(F128::zero(), |acc, (a, b)| acc + *a * b);
+
+        let mask = ZkMask::rand(rng, num_vars, 2);
+        let mask_sum = mask.sum();
+        let rho = F128::rand(rng);
+
+        let mut prover = ZkSumcheck::new(
+            Prodcheck::new(vec![p], vec![q], initial_claim, true, false),
+            initial_claim,
+            mask.clone(),
+            rho
+        );
+
+        let mut claim = masked_initial_claim(initial_claim, mask_sum, rho);
+        for _ in 0..num_vars {
+            let round_poly = prover.round_msg().coeffs(claim);
+            assert!(round_poly.len() == 3);
+            let challenge = F128::rand(rng);
+            claim = evaluate_univar(&round_poly, challenge);
+            prover.bind(challenge);
+        }
+
+        let challenges = prover.challenges.clone();
+        let (inner, mask_eval) = prover.finish();
+        assert!(mask_eval == mask.evaluate(&challenges));
+        let ProdcheckOutput { p_evs, q_evs } = inner.finish();
+        assert!(unmask_final_claim(claim, mask_eval, rho) == p_evs[0] * q_evs[0]);
+    }
+
+    #[test]
+    fn mask_sum_as_expected() {
+        let rng = &mut OsRng;
+        let num_vars = 5;
+        let mask = ZkMask::rand(rng, num_vars, 3);
+        let sum = (0 .. 1 << num_vars).map(|x: usize| {
+            let pt : Vec<_> = (0..num_vars).map(|i| F128::new((x >> i) & 1 == 1)).collect();
+            mask.evaluate(&pt)
+        }).fold(F128::zero(), |a, b| a + b);
+        assert!(sum == mask.sum());
+    }
+
+    #[test]
+    #[should_panic]
+    fn constant_mask_is_rejected() {
+        ZkMask::new(vec![vec![F128::one()], vec![F128::one(), F128::one()]]);
+    }
+
+    #[test]
+    fn committed_poly_opens_to_rows() {
+        let rng = &mut OsRng;
+        let (num_vars, degree) = (5, 2);
+        let mask = ZkMask::rand(rng, num_vars, degree);
+        let poly = mask.committed_poly();
+        assert!(poly.len() == 1 << ZkMask::committed_vars(num_vars, degree));
+        for i in 0..num_vars {
+            let r = F128::rand(rng);
+            assert!(evaluate(&poly, &mask_opening_point(i, r, num_vars, degree)) == evaluate_univar(&mask.coeffs[i], r));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn mask_of_low_degree_is_rejected() {
+        let rng = &mut OsRng;
+        let num_vars = 4;
+        let p : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let q = p.clone();
+        let claim = p.iter().fold(F128::zero(), |acc, a| acc + *a * a);
+        let prodcheck = Prodcheck::new(vec![p], vec![q], claim, true, false);
+        let mut prover = ZkSumcheck::new(prodcheck, claim, ZkMask::rand(rng, num_vars, 1), F128::rand(rng));
+        prover.round_msg();
+    }
+
+    /// Returns the claim, the proof and the final claim of the wrapped prodcheck.
+    fn zk_prodcheck_proof(num_vars: usize, pcs: &Ligero) -> (F128, ZkSumcheckProof<LigeroCommitment, LigeroProof>, F128) {
+        let rng = &mut OsRng;
+        let p : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let q : Vec<_> = (0 .. 1 << num_vars).map(|_| F128::rand(rng)).collect();
+        let claim = p.iter().zip(q.iter()).fold(F128::zero(), |acc, (a, b)| acc + *a * b);
+
+        let prodcheck = Prodcheck::new(vec![p], vec![q], claim, true, false);
+        let (proof, inner, _) = prove_sumcheck_zk(prodcheck, claim, num_vars, 2, pcs, rng, &mut Transcript::new(b"zk"));
+        let ProdcheckOutput { p_evs, q_evs } = inner.finish();
+        (claim, proof, p_evs[0] * q_evs[0])
+    }
+
+    #[test]
+    fn zk_sumcheck_with_transcript_works() {
+        let num_vars = 8;
+        let pcs = Ligero::new(ZkMask::committed_vars(num_vars, 2), 2, 1, 40);
+        let (claim, proof, final_claim) = zk_prodcheck_proof(num_vars, &pcs);
+        let (expected, _) = verify_sumcheck_zk(claim, &proof, 2, &pcs, &mut Transcript::new(b"zk")).unwrap();
+        assert!(expected == final_claim);
+    }
+
+    #[test]
+    fn wrong_mask_opening_is_rejected() {
+        let num_vars = 8;
+        let pcs = Ligero::new(ZkMask::committed_vars(num_vars, 2), 2, 1, 40);
+        let (claim, mut proof, _) = zk_prodcheck_proof(num_vars, &pcs);
+        proof.row_evals[3] += F128::one();
+        assert!(verify_sumcheck_zk(claim, &proof, 2, &pcs, &mut Transcript::new(b"zk")).is_none());
+    }
+}
@@ -1,9 +1,9 @@
 use std::{iter::once, mem::{MaybeUninit}};
-use num_traits::Zero;
+use num_traits::{One, Zero};
 use bytemuck;
 use rayon::iter::IntoParallelIterator;
 
-use crate::{field::F128, ptr_utils::UninitArr};
+use crate::{field::F128, protocols::utils::{evaluate_univar, interpolate_univar}, ptr_utils::UninitArr};
 
 #[derive(Clone, Debug)]
 pub struct CompressedPoly {
@@ -30,6 +30,105 @@ impl CompressedPoly {
 
         once(coeff_0).chain(once(coeff_1)).chain(self.compressed_coeffs[1..].iter().map(|x|*x)).collect()
     }
+
+    /// Degree of the polynomial (which is the number of stored coefficients, as the linear one is skipped).
+    pub fn degree(&self) -> usize {
+        self.compressed_coeffs.len()
+    }
+
+    /// Verifier side: rejects the message if it has wrong degree. Malicious prover could otherwise send a polynomial
+    /// of higher degree, which breaks soundness.
+    pub fn check_degree(&self, degree: usize) {
+        assert!(self.degree() == degree, "Round polynomial has degree {}, expected {}.", self.degree(), degree);
+    }
+
+    /// Same as coeffs, but rejects the message if it has wrong degree.
+    pub fn coeffs_with_degree(&self, sum: F128, degree: usize) -> Vec<F128> {
+        self.check_degree(degree);
+        self.coeffs(sum)
+    }
+
+    /// Evaluates the polynomial without decompressing it.
+    /// P(t) = c_0 + c_1 t + sum_{k > 1} c_k t^k, and c_1 = sum + sum_{k > 1} c_k, so P(t) = c_0 + sum * t + sum_{k > 1} c_k (t^k + t).
+    pub fn evaluate(&self, sum: F128, at: F128) -> F128 {
+        let mut ret = F128::zero();
+        let mut high_sum = F128::zero();
+        for c in self.compressed_coeffs[1..].iter().rev() {
+            ret += c;
+            ret *= at;
+            high_sum += c;
+        }
+        ret *= at;
+        ret + self.compressed_coeffs[0] + (sum + high_sum) * at
+    }
+
+    /// Same as evaluate, but rejects the message if it has wrong degree.
+    pub fn evaluate_with_degree(&self, sum: F128, at: F128, degree: usize) -> F128 {
+        self.check_degree(degree);
+        self.evaluate(sum, at)
+    }
+
+    pub fn to_evaluation_form(&self, sum: F128) -> EvaluationPoly {
+        let points = EvaluationPoly::points(self.degree());
+        let evals = points.iter().map(|x| self.evaluate(sum, *x)).collect();
+        EvaluationPoly { evals }
+    }
+}
+
+/// Alternative encoding of a round polynomial of degree d, by its values in 0, 2, 3, ..., d (elements of F128 with
+/// these raw representations, note that raw 1 is not the unity). Value in 1 is skipped, because it is recovered from
+/// the claim P(0) + P(1).
+/// It has the same size as CompressedPoly, but can be cheaper for provers which compute round polynomials by
+/// evaluation.
+#[derive(Clone, Debug)]
+pub struct EvaluationPoly {
+    pub evals: Vec<F128>,
+}
+
+impl EvaluationPoly {
+    /// Points in which the stored values are taken, i.e. 0, 2, ..., degree.
+    pub fn points(degree: usize) -> Vec<F128> {
+        assert!(degree > 0);
+        once(0).chain(2 .. degree as u128 + 1).map(|x| F128::from_raw(x)).collect()
+    }
+
+    /// Points 0, 1, 2, ..., degree, with 1 being the unity of the field.
+    pub fn all_points(degree: usize) -> Vec<F128> {
+        let mut points = Self::points(degree);
+        points.insert(1, F128::one());
+        points
+    }
+
+    /// Takes values of the polynomial in all_points(degree). Returns encoded polynomial and the sum P(0) + P(1).
+    pub fn from_evals(evals: &[F128]) -> (Self, F128) {
+        assert!(evals.len() > 1);
+        let sum = evals[0] + evals[1];
+        (Self{evals: once(&evals[0]).chain(evals[2..].iter()).map(|x|*x).collect()}, sum)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.evals.len()
+    }
+
+    pub fn check_degree(&self, degree: usize) {
+        assert!(self.degree() == degree, "Round polynomial has degree {}, expected {}.", self.degree(), degree);
+    }
+
+    /// Recovers coefficients of the polynomial using the previous claim.
+    pub fn coeffs(&self, sum: F128) -> Vec<F128> {
+        let points = Self::all_points(self.degree());
+        let mut values = self.evals.clone();
+        values.insert(1, self.evals[0] + sum);
+        interpolate_univar(&points, &values)
+    }
+
+    pub fn evaluate(&self, sum: F128, at: F128) -> F128 {
+        evaluate_univar(&self.coeffs(sum), at)
+    }
+
+    pub fn to_compressed(&self, sum: F128) -> CompressedPoly {
+        CompressedPoly::compress(&self.coeffs(sum)).0
+    }
 }
 
 /// This describes a matrix from I arrays of size 2^logsize_in, to O arrays of size 2^logsize_outp 
@@ -152,4 +251,44 @@ pub trait SumcheckObject {
 //     fn round(&mut self, msg: Self::RoundResponse, challenge: F128);
 //     fn finish(self, final_claim: Self::FinalClaim);
 
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn compressed_poly_evaluate() {
+        let rng = &mut OsRng;
+        for degree in 1..5 {
+            let poly : Vec<_> = (0..degree + 1).map(|_| F128::rand(rng)).collect();
+            let (compressed, sum) = CompressedPoly::compress(&poly);
+            assert!(compressed.degree() == degree);
+            let x = F128::rand(rng);
+            assert!(compressed.evaluate(sum, x) == evaluate_univar(&poly, x));
+            assert!(compressed.coeffs_with_degree(sum, degree) == poly);
+
+            let evals = compressed.to_evaluation_form(sum);
+            assert!(evals.degree() == degree);
+            assert!(evals.coeffs(sum) == poly);
+            assert!(evals.evaluate(sum, x) == evaluate_univar(&poly, x));
+            assert!(evals.to_compressed(sum).compressed_coeffs == compressed.compressed_coeffs);
+
+            let all_evals : Vec<_> = EvaluationPoly::all_points(degree).iter().map(|x| evaluate_univar(&poly, *x)).collect();
+            let (from_evals, sum_) = EvaluationPoly::from_evals(&all_evals);
+            assert!(sum_ == sum);
+            assert!(from_evals.coeffs(sum) == poly);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn compressed_poly_rejects_wrong_degree() {
+        let rng = &mut OsRng;
+        let poly : Vec<_> = (0..4).map(|_| F128::rand(rng)).collect();
+        let (compressed, sum) = CompressedPoly::compress(&poly);
+        compressed.coeffs_with_degree(sum, 2);
+    }
+}
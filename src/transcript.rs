@@ -0,0 +1,96 @@
+// Fiat-Shamir transcript, based on Keccak-256.
+
+// The state is a single digest. Absorbing data replaces the state with the hash of the state and the data, and every
+// squeezed challenge is derived from the state, which is then updated, so that consecutive challenges are independent.
+
+use bytemuck::cast_slice;
+
+use crate::{field::F128, hash::{challenges_from_seed, keccak256}, merkle::Digest, traits::{CompressedPoly, SumcheckObject}};
+
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    state: Digest,
+}
+
+impl Transcript {
+    pub fn new(domain: &[u8]) -> Self {
+        Self { state: keccak256(domain) }
+    }
+
+    pub fn absorb_bytes(&mut self, data: &[u8]) {
+        let mut input = self.state.to_vec();
+        input.extend_from_slice(data);
+        self.state = keccak256(&input);
+    }
+
+    pub fn absorb(&mut self, data: &[F128]) {
+        self.absorb_bytes(cast_slice(data));
+    }
+
+    pub fn absorb_poly(&mut self, poly: &CompressedPoly) {
+        self.absorb(&poly.compressed_coeffs);
+    }
+
+    pub fn challenges(&mut self, n: usize) -> Vec<F128> {
+        let ret = challenges_from_seed(&self.state, 0, n);
+        self.absorb_bytes(&[1]);
+        ret
+    }
+
+    pub fn challenge(&mut self) -> F128 {
+        self.challenges(1)[0]
+    }
+}
+
+/// Prover side of non-interactive sumcheck: absorbs round messages and binds the object to the squeezed challenges.
+/// Returns round messages and challenges. Zero-knowledge version is protocols::zk::prove_sumcheck_zk.
+pub fn prove_sumcheck<S: SumcheckObject>(object: &mut S, num_rounds: usize, transcript: &mut Transcript) -> (Vec<CompressedPoly>, Vec<F128>) {
+    let mut msgs = Vec::with_capacity(num_rounds);
+    let mut rs = Vec::with_capacity(num_rounds);
+    for _ in 0..num_rounds {
+        let msg = object.round_msg();
+        transcript.absorb_poly(&msg);
+        let r = transcript.challenge();
+        object.bind(r);
+        msgs.push(msg);
+        rs.push(r);
+    }
+    (msgs, rs)
+}
+
+/// Verifier side of non-interactive sumcheck. Returns the final claim and the challenges, or None if some round
+/// message has wrong degree.
+pub fn verify_sumcheck(mut claim: F128, msgs: &[CompressedPoly], degree: usize, transcript: &mut Transcript) -> Option<(F128, Vec<F128>)> {
+    let mut rs = Vec::with_capacity(msgs.len());
+    for msg in msgs {
+        if msg.degree() != degree {
+            return None;
+        }
+        transcript.absorb_poly(msg);
+        let r = transcript.challenge();
+        claim = msg.evaluate(claim, r);
+        rs.push(r);
+    }
+    Some((claim, rs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcript_is_deterministic() {
+        let mut a = Transcript::new(b"test");
+        let mut b = Transcript::new(b"test");
+        let data = [F128::from_raw(3), F128::from_raw(5)];
+        a.absorb(&data);
+        b.absorb(&data);
+        let (x, y) = (a.challenge(), b.challenge());
+        assert!(x == y);
+        assert!(a.challenge() != x);
+
+        let mut c = Transcript::new(b"test");
+        c.absorb(&[F128::from_raw(3)]);
+        assert!(c.challenge() != x);
+    }
+}